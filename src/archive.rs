@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::ArchiveError;
+
+/// Check if `path` names a zip archive rather than a bare ROM
+pub fn is_zip_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// Read a ROM out of the zip archive at `path`.
+/// `entry` is the name of the ROM within the archive; it can be left out when the
+/// archive holds a single `.ch8` file, otherwise the names found are returned in the error.
+pub fn read_zipped_rom(path: &Path, entry: Option<&str>) -> Result<Vec<u8>, ArchiveError> {
+    let unreadable = |error: &dyn std::error::Error| ArchiveError::Unreadable(error.to_string());
+    let file = File::open(path).map_err(|error| unreadable(&error))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|error| unreadable(&error))?;
+
+    let name = match entry {
+        Some(entry) => entry.to_string(),
+        None => {
+            let mut roms: Vec<String> = archive
+                .file_names()
+                .filter(|name| {
+                    Path::new(name)
+                        .extension()
+                        .is_some_and(|extension| extension.eq_ignore_ascii_case("ch8"))
+                })
+                .map(String::from)
+                .collect();
+            match roms.len() {
+                0 => return Err(ArchiveError::NoRom),
+                1 => roms.remove(0),
+                _ => {
+                    roms.sort();
+                    return Err(ArchiveError::SeveralRoms(roms));
+                }
+            }
+        }
+    };
+
+    let mut file = match archive.by_name(&name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Err(ArchiveError::MissingEntry(name)),
+        Err(error) => return Err(unreadable(&error)),
+    };
+    let mut rom = Vec::new();
+    file.read_to_end(&mut rom)
+        .map_err(|error| unreadable(&error))?;
+    Ok(rom)
+}
@@ -1,52 +1,216 @@
-use sdl2::audio::{AudioCallback, AudioSpecDesired};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::Sdl;
+use std::f32::consts::{FRAC_PI_4, TAU};
 use std::sync::{Arc, Mutex};
 
+use crate::AUDIO_PATTERN_SIZE;
+
+/// Shape of the beep sound wave
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    #[default]
+    Square, // the classic harsh buzzer
+    Sine,     // a gentle pure tone
+    Triangle, // softer than square, brighter than sine
+    Sawtooth, // a bright buzzing tone
+}
+
+impl Waveform {
+    /// Get a waveform from its name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            "sawtooth" => Some(Waveform::Sawtooth),
+            _ => None,
+        }
+    }
+
+    /// Amplitude between -1 and 1 at `phase`, the position within one period from 0 to 1
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+pub const DEFAULT_BEEP_FREQUENCY: f32 = 440.0; // pitch of the beep in Hz
+pub const DEFAULT_VOLUME: f32 = 0.25; // loudness of the beep, from 0 to 1
+pub const MIN_AUDIO_BUFFER: u16 = 16; // smaller buffers underrun on any system
+pub const MAX_AUDIO_BUFFER: u16 = 8192; // about 190 ms at 44.1 kHz, larger buffers make the beep lag
+pub const SAMPLE_RATE: i32 = 44100; // samples per second asked of SDL
+
 // Struct defining the beep sound wave
-pub struct SquareWave {
+pub struct Beep {
+    waveform: Waveform,
     phase: f32,
+    frequency: f32,   // pitch of the beep in Hz
+    sample_rate: f32, // samples per second of the audio device
     volume: f32,
+    pan: f32,        // position of the beep from -1 (left) to 1 (right)
+    channels: usize, // interleaved channels of the audio device, 2 for stereo
+    pattern: Option<[u8; AUDIO_PATTERN_SIZE]>, // XO-CHIP 1-bit samples played instead of the waveform
+    pattern_rate: f32,                         // XO-CHIP pattern bits played per second
+    pattern_position: f32,                     // position in the pattern, in bits
 }
 
-impl AudioCallback for SquareWave {
+impl Beep {
+    /// Move the beep between the left (-1) and right (1) speakers, 0 being centered
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
+
+    /// Play the 128 1-bit samples of an XO-CHIP audio pattern in a loop, at `rate` bits
+    /// per second, instead of the waveform. `None` goes back to the waveform.
+    pub fn set_pattern(&mut self, pattern: Option<[u8; AUDIO_PATTERN_SIZE]>, rate: f32) {
+        self.pattern = pattern;
+        self.pattern_rate = rate;
+    }
+
+    /// Function to get the next sample, from the XO-CHIP pattern when one is set
+    fn next_sample(&mut self) -> f32 {
+        match &self.pattern {
+            Some(pattern) => {
+                let bits = (AUDIO_PATTERN_SIZE * 8) as f32;
+                self.pattern_position =
+                    (self.pattern_position + self.pattern_rate / self.sample_rate) % bits;
+                let bit = self.pattern_position as usize;
+                if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            None => {
+                // Advance by the fraction of a period that one sample lasts, so the pitch
+                // is the requested frequency whatever the sample rate of the device
+                self.phase = (self.phase + self.frequency / self.sample_rate) % 1.0;
+                self.waveform.sample(self.phase)
+            }
+        }
+    }
+
+    /// Function to get the gains of the left and right channels.
+    /// Constant power panning keeps the loudness the same wherever the beep is.
+    fn channel_gains(&self) -> (f32, f32) {
+        let angle = (self.pan + 1.0) * FRAC_PI_4;
+        (angle.cos(), angle.sin())
+    }
+}
+
+impl AudioCallback for Beep {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [Self::Channel]) {
-        for x in out.iter_mut() {
-            // Generate a square wave
-            self.phase = (self.phase + 0.02) % 1.0;
-            *x = if self.phase < 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
+        let (left, right) = self.channel_gains();
+        // One frame holds a sample for each channel
+        for frame in out.chunks_mut(self.channels) {
+            // Generate the selected wave
+            let sample = self.volume * self.next_sample();
+            match frame {
+                [left_sample, right_sample, ..] => {
+                    *left_sample = sample * left;
+                    *right_sample = sample * right;
+                }
+                [mono] => *mono = sample, // the device has no stereo, panning is ignored
+                [] => {}
+            }
         }
     }
 }
 
-pub fn initialize_audio() -> (sdl2::audio::AudioDevice<SquareWave>, Arc<Mutex<bool>>) {
-    let sdl_context = sdl2::init().unwrap();
-    let audio_subsystem = sdl_context.audio().unwrap();
+/// Check that the audio buffer size is a power of two, as SDL prefers, between
+/// `MIN_AUDIO_BUFFER` and `MAX_AUDIO_BUFFER` samples
+pub fn is_valid_audio_buffer_size(samples: u16) -> bool {
+    samples.is_power_of_two() && (MIN_AUDIO_BUFFER..=MAX_AUDIO_BUFFER).contains(&samples)
+}
+
+/// Check that the beep frequency is a positive number of Hz below half the sample rate,
+/// the highest pitch the samples can carry
+pub fn is_valid_beep_frequency(frequency: f32) -> bool {
+    frequency.is_finite() && frequency > 0.0 && frequency < SAMPLE_RATE as f32 / 2.0
+}
+
+/// Initialize the audio device from the SDL context shared with the display.
+/// `buffer_size` is the number of samples per audio buffer: a small buffer makes the
+/// beep start and stop with less delay, but too small a buffer may underrun and crackle.
+/// `frequency` is the pitch of the beep in Hz, `volume` its loudness from 0 to 1
+/// and `pan` its position from -1 (left) to 1 (right).
+pub fn initialize_audio(
+    sdl_context: &Sdl,
+    buffer_size: u16,
+    waveform: Waveform,
+    frequency: f32,
+    volume: f32,
+    pan: f32,
+) -> Result<(sdl2::audio::AudioDevice<Beep>, Arc<Mutex<bool>>), String> {
+    let audio_subsystem = sdl_context.audio()?;
 
     // Audio spec
     let spec = AudioSpecDesired {
-        freq: Some(44100),
-        channels: Some(1), // Mono
-        samples: None,     // Default sample size
+        freq: Some(SAMPLE_RATE),
+        channels: Some(2),          // Stereo, for panning
+        samples: Some(buffer_size), // Requested buffer size
     };
 
     // Shared state to control playback
     let is_playing = Arc::new(Mutex::new(false));
 
     // Create an audio device
-    let device = audio_subsystem
-        .open_playback(None, &spec, |_| {
-            // Initialize the SquareWave generator
-            SquareWave {
-                phase: 0.0,
-                volume: 0.25,
-            }
-        })
-        .unwrap();
+    let device = audio_subsystem.open_playback(None, &spec, |spec| {
+        // Initialize the beep generator for the sample rate and channels SDL actually gave us
+        Beep {
+            waveform,
+            phase: 0.0,
+            frequency,
+            sample_rate: spec.freq as f32,
+            volume: volume.clamp(0.0, 1.0),
+            pan: pan.clamp(-1.0, 1.0),
+            channels: spec.channels.max(1) as usize,
+            pattern: None,
+            pattern_rate: 0.0,
+            pattern_position: 0.0,
+        }
+    })?;
 
-    (device, is_playing)
+    Ok((device, is_playing))
+}
+
+/// Make the beep play an XO-CHIP audio pattern at `rate` bits per second.
+/// A pattern of zeros, the state before any F002, keeps the regular beep.
+pub fn update_audio_pattern(
+    audio_device: &mut AudioDevice<Beep>,
+    pattern: &[u8; AUDIO_PATTERN_SIZE],
+    rate: f32,
+) {
+    let pattern = Some(*pattern).filter(|pattern| pattern.iter().any(|&byte| byte != 0));
+    audio_device.lock().set_pattern(pattern, rate);
+}
+
+/// Start or stop the beep to match the CPU's sound state
+pub fn update_sound(
+    audio_device: &AudioDevice<Beep>,
+    is_playing: &Arc<Mutex<bool>>,
+    beeping: bool,
+) {
+    let mut playing = is_playing.lock().unwrap();
+    if beeping && !*playing {
+        // Start playing sound if not already playing
+        audio_device.resume();
+        *playing = true;
+    } else if !beeping && *playing {
+        // Stop playing sound if timer reaches 0
+        audio_device.pause();
+        *playing = false;
+    }
 }
@@ -0,0 +1,81 @@
+use crate::{Chip8, Quirks, DEFAULT_MEMORY_SIZE, PROGRAM_START};
+
+/// Step by step configuration of a `Chip8`, e.g.
+/// `Chip8::builder().quirks(Quirks::super_chip()).clock_speed(1000).build()`.
+/// Options that are not set keep the defaults of `Chip8::new`.
+#[derive(Debug, Clone)]
+pub struct Chip8Builder {
+    quirks: Quirks,           // compatibility behaviours of the emulated interpreter
+    clock_speed: Option<u64>, // instructions per second, the CPU default when unset
+    memory_size: usize,       // bytes of memory
+    program_start: u16,       // address where ROMs are loaded and execution starts
+    seed: Option<u64>,        // seed of the CXNN random numbers, unpredictable when unset
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Self {
+        Self {
+            quirks: Quirks::default(),
+            clock_speed: None,
+            memory_size: DEFAULT_MEMORY_SIZE,
+            program_start: PROGRAM_START as u16,
+            seed: None,
+        }
+    }
+}
+
+impl Chip8Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the quirks of the emulated interpreter
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Set the number of instructions executed per second
+    pub fn clock_speed(mut self, clock_speed: u64) -> Self {
+        self.clock_speed = Some(clock_speed);
+        self
+    }
+
+    /// Set the size of the memory in bytes, up to 64 KB
+    pub fn memory_size(mut self, memory_size: usize) -> Self {
+        self.memory_size = memory_size;
+        self
+    }
+
+    /// Set the address where ROMs are loaded and execution starts
+    pub fn program_start(mut self, addr: u16) -> Self {
+        self.program_start = addr;
+        self
+    }
+
+    /// Set the seed of the random numbers, to get the same ones on every run
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Create the CPU. Panics if the memory size or program start are out of range.
+    pub fn build(self) -> Chip8 {
+        let mut chip8 = Chip8::with_memory_size(self.quirks, self.memory_size);
+        chip8.set_program_start(self.program_start);
+        if let Some(clock_speed) = self.clock_speed {
+            chip8.set_clock_speed(clock_speed);
+        }
+        if let Some(seed) = self.seed {
+            chip8.set_seed(seed);
+        }
+        chip8
+    }
+}
+
+impl Chip8 {
+    /// A function to start configuring a CPU with a `Chip8Builder`
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::new()
+    }
+}
@@ -1,17 +1,25 @@
-use crate::SquareWave;
-use rand::Rng;
-use sdl2::audio::AudioDevice;
-use std::sync::{Arc, Mutex};
+#[cfg(feature = "serde")]
+use crate::StateError;
+use crate::{
+    Chip8Error, Framebuffer, InputEvent, LoadError, MemoryAccess, OpCode, Quirks, Recorder,
+    WatchHit, ALL_PLANES, OPCODE_CATEGORIES, PLANE_1, PLANE_2,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::collections::{BTreeSet, VecDeque};
 use std::time::{Duration, Instant};
 
-const MEMORY_SIZE: usize = 4096; // 4 KB of memory
+pub const DEFAULT_MEMORY_SIZE: usize = 4096; // 4 KB of memory, as on the COSMAC VIP
+pub const MAX_MEMORY_SIZE: usize = 0x10000; // 64 KB, everything a 16-bit address can reach
 const STACK_SIZE: usize = 16; // Stack can hold 16 addresses
 const NUM_REGISTERS: usize = 16; // 16 general-purpose registers
-const DISPLAY_WIDTH: usize = 64; // Default display width
-const DISPLAY_HEIGHT: usize = 32; // Default pixel height
+pub const NUM_KEYS: usize = 16; // hexadecimal keypad with keys 0 to F
+#[cfg(feature = "serde")]
+pub const DEFAULT_HISTORY_CAPACITY: usize = 600; // about a second of rewind at the default clock speed
+const STACK_DUMP_DEPTH: usize = 4; // stack entries shown by dump_state
 const FONT_START: usize = 0x050; // Font starts at memory location 0x050
 const FONT_SIZE: usize = 80; // 16 characters * 5 bytes per character
-const FONTS: [u8; FONT_SIZE] = [
+pub const FONTS: [u8; FONT_SIZE] = [
     // Each number is represented as 5 bytes, 4 pixels wide
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -30,146 +38,906 @@ const FONTS: [u8; FONT_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
-const PROGRAM_START: usize = 0x200;
-const TIMER_FREQUENCY: u64 = 60; // Timer runs at 60 Hz (FPS)
-const TIMER_INTERVAL: Duration = Duration::from_micros(1_000_000 / TIMER_FREQUENCY); // should be updated 60 times per second to get 60 FPS
+
+/// Subscriber to the instruction trace, receiving the address of the instruction,
+/// the raw opcode and a description
+pub type TraceHook = Box<dyn FnMut(u16, u16, &str)>;
+
+/// What a DXYN instruction drew, given to the draw trace hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrawTrace {
+    pub sprite: Vec<u8>,     // sprite bytes read from memory at I
+    pub x: usize,            // column of the top-left corner of the sprite
+    pub y: usize,            // row of the top-left corner of the sprite
+    pub pixels_set: u32,     // pixels turned on by the sprite
+    pub pixels_cleared: u32, // pixels turned off by the sprite
+    pub collision: bool,     // whether VF was set
+}
+
+/// Subscriber to the DXYN draws, e.g. to chase a sprite rendering bug
+pub type DrawTraceHook = Box<dyn FnMut(&DrawTrace)>;
+
+/// Describe the instruction being executed to the trace hook, if one is set.
+/// The description is only formatted when somebody is listening.
+macro_rules! trace {
+    ($chip8:expr, $address:expr, $opcode:expr, $($arg:tt)*) => {
+        if let Some(hook) = $chip8.trace_hook.as_mut() {
+            hook($address, $opcode, &format!($($arg)*));
+        }
+    };
+}
+
+pub const PROGRAM_START: usize = 0x200; // default start address, ETI-660 programs use 0x600 instead
+const DEFAULT_CLOCK_SPEED: u64 = 700; // 700 Chip-8 instructions per second
+pub const DEFAULT_TIMER_FREQUENCY: u64 = 60; // Timer runs at 60 Hz (FPS)
+pub const AUDIO_PATTERN_SIZE: usize = 16; // XO-CHIP audio pattern of 128 1-bit samples
+const DEFAULT_PITCH: u8 = 64; // XO-CHIP pitch giving a 4000 Hz playback rate
+
+/// How the DXYN opcode combines sprite pixels with the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrawMode {
+    /// Standard CHIP-8 behaviour: sprite pixels are XORed onto the display
+    /// and VF is set when a lit pixel gets erased.
+    #[default]
+    Xor,
+    /// Non-standard behaviour used by a few experimental ROMs and tools:
+    /// sprite pixels are written directly, so nothing is ever erased.
+    /// VF is still set when a sprite pixel lands on an already lit pixel.
+    Overwrite,
+}
+
 pub struct Chip8 {
-    memory: [u8; MEMORY_SIZE], // 4 KB of memory
-    // NB: the dimensioning is w*h; width represents the columns, and height represents the rows
-    // This is a bit confusing for now.
-    pub display: [[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT], // 64x32 monochrome display
-    program_counter: u16,           // Program counter (PC), 12-bit addressable
-    index_register: u16,            // index register (I), 12-bit addressable
-    stack: [u16; STACK_SIZE],       // Stack for 16-bit addresses
-    delay_timer: u8,                // 8-bit delay timer
-    sound_timer: u8,                // 8-bit sound timer
+    memory: Vec<u8>,                            // 4 KB of memory by default, up to 64 KB
+    pub display: Framebuffer, // 64x32 (or 128x64) display with up to two bitplanes
+    program_counter: u16,     // Program counter (PC), 12-bit addressable
+    index_register: u16,      // index register (I), 12-bit addressable
+    stack: [u16; STACK_SIZE], // Stack for 16-bit addresses
+    delay_timer: u8,          // 8-bit delay timer
+    sound_timer: u8,          // 8-bit sound timer
     registers: [u8; NUM_REGISTERS], // 16 8-bit general-purpose registers (V0-VF)
-    last_timer_update: Instant,     // parameter to work with timer update
-    stack_pointer: usize,           // parameter for tracking the position on the stack during calls
-    pub keypad: [bool; 16],         // bool array to hold the key information
-    modern: bool,                   // bool to determine if to use modern implementation or not
+    last_timer_update: Option<Instant>, // last timer decrement by `update_timers`, unset until its first call
+    stack_pointer: usize, // parameter for tracking the position on the stack during calls
+    keypad: [bool; NUM_KEYS], // bool array to hold the key information
+    quirks: Quirks,       // compatibility behaviours of the emulated interpreter
+    draw_mode: DrawMode,  // how sprites are combined with the display
+    draw_trace_hook: Option<DrawTraceHook>, // optional subscriber to the DXYN draws
+    debug: bool,          // bool to determine if debugging features are active
+    break_opcode: Option<u16>, // magic opcode that requests a debugger break
+    break_requested: bool, // set when the break opcode was executed in debug mode
+    steps_until_break: Option<usize>, // instructions left to run before breaking again
+    run_to_address: Option<u16>, // one-shot address to break at
+    opcode_histogram: [u64; OPCODE_CATEGORIES], // executed instructions per opcode category
+    clock_speed: u64,     // number of instructions executed per second
+    hi_res: bool,         // bool to determine if the 128x64 SUPER-CHIP resolution is active
+    rom: Vec<u8>,         // the last ROM loaded, kept to restart the game on reset
+    trace_hook: Option<TraceHook>, // optional subscriber to the instruction trace
+    planes: u8,           // XO-CHIP bitplanes selected for drawing, clearing and scrolling
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE], // XO-CHIP 1-bit audio pattern loaded by F002
+    pitch: u8,            // XO-CHIP playback rate of the audio pattern set by FX3A
+    waiting_for_vblank: bool, // set by a draw with the display_wait quirk until the next timer tick
+    program_start: u16,   // address where ROMs are loaded and execution starts
+    display_dirty: bool,  // set when the display changed since it was last taken for drawing
+    #[cfg(feature = "serde")]
+    history: VecDeque<Vec<u8>>, // recent save states, oldest first, used to rewind
+    #[cfg(feature = "serde")]
+    history_capacity: usize, // number of save states kept for rewinding, 0 when disabled
+    pressed_key: Option<u8>, // key pressed during FX0A, the instruction completes on its release
+    last_collision_count: u32, // sprite pixels that hit lit pixels in the last DXYN
+    instruction_count: u64, // instructions executed since the CPU was created
+    halted: bool, // set when the ROM jumped to the jump itself, an infinite loop ending the program
+    timer_frequency: u64, // number of times per second the delay and sound timers are decremented
+    key_presses: VecDeque<u8>, // keys in the order they were pressed, oldest first, for FX0A
+    rng: ChaCha12Rng, // random numbers for CXNN
+    seed: Option<u64>, // seed of the random numbers, reused on reset to replay the same sequence
+    recorder: Option<Recorder>, // keypad input logged while recording
+    replay: VecDeque<InputEvent>, // recorded keypad input left to replay, oldest first
+    input_start: u64, // instruction count when the recording or the replay started
+    waiting_for_key: bool, // set while FX0A has not been satisfied by a key press and release
+    breakpoints: BTreeSet<u16>, // addresses where a break is requested before executing them
+    stopped_at: Option<u16>, // address a break was taken at, its breakpoint lets it run on the next step
+    watchpoints: BTreeSet<u16>, // addresses whose reads and writes by instructions request a break
+    watch_hits: Vec<WatchHit>, // watchpoint accesses since they were last taken
 }
 
 impl Default for Chip8 {
     fn default() -> Self {
         Self {
-            memory: [0; MEMORY_SIZE],
-            display: [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT], // screen starts black
+            memory: vec![0; DEFAULT_MEMORY_SIZE],
+            display: Framebuffer::new(),           // screen starts black
             program_counter: PROGRAM_START as u16, // offset to the default start address (200 in hex)
             index_register: 0,
             stack: [0; STACK_SIZE],
             delay_timer: 0,
             sound_timer: 0,
             registers: [0; NUM_REGISTERS],
-            last_timer_update: Instant::now(), // set counter to instance CPU is created
-            stack_pointer: 0,                  // stack starts at zero
-            keypad: [false; 16],               // all keys start as unpressed
-            modern: false,                     // determine if the modern implementation is used
+            last_timer_update: None, // the wall clock is only read by `update_timers`, WebAssembly has none
+            stack_pointer: 0,        // stack starts at zero
+            keypad: [false; NUM_KEYS], // all keys start as unpressed
+            quirks: Quirks::default(), // behave like the original COSMAC VIP
+            draw_mode: DrawMode::Xor, // standard XOR drawing
+            draw_trace_hook: None,   // draws are not logged by default
+            debug: false,            // debugging is off by default
+            break_opcode: None,      // no break opcode configured
+            break_requested: false,
+            steps_until_break: None,
+            run_to_address: None,
+            opcode_histogram: [0; OPCODE_CATEGORIES],
+            clock_speed: DEFAULT_CLOCK_SPEED,
+            hi_res: false, // start in the standard 64x32 resolution
+            rom: Vec::new(),
+            trace_hook: None, // no logging by default
+            planes: PLANE_1,  // only the first plane, as on CHIP-8
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH, // plays the pattern at 4000 Hz
+            waiting_for_vblank: false,
+            program_start: PROGRAM_START as u16,
+            display_dirty: true, // the blank screen still has to be drawn once
+            #[cfg(feature = "serde")]
+            history: VecDeque::new(),
+            #[cfg(feature = "serde")]
+            history_capacity: 0, // rewinding is off by default
+            pressed_key: None,
+            last_collision_count: 0,
+            instruction_count: 0,
+            halted: false,
+            timer_frequency: DEFAULT_TIMER_FREQUENCY,
+            key_presses: VecDeque::new(),
+            rng: ChaCha12Rng::from_os_rng(), // unpredictable unless a seed is given
+            seed: None,
+            recorder: None,
+            replay: VecDeque::new(),
+            input_start: 0,
+            waiting_for_key: false,
+            breakpoints: BTreeSet::new(),
+            stopped_at: None,
+            watchpoints: BTreeSet::new(),
+            watch_hits: Vec::new(),
         }
     }
 }
 
 impl Chip8 {
-    pub fn new(modern: bool) -> Self {
+    pub fn new(quirks: Quirks) -> Self {
         let mut chip8 = Chip8 {
-            modern,
+            quirks,
             ..Default::default()
         };
-        chip8.modern = modern;
 
-        // Load font data into memory at 0x050
-        for (i, font) in FONTS.iter().enumerate() {
-            chip8.memory[FONT_START + i] = *font;
-        }
+        chip8.load_fonts();
+        chip8
+    }
 
+    /// A function to create a CPU whose CXNN random numbers are generated from `seed`,
+    /// so that a ROM receiving the same input behaves identically on every run
+    pub fn new_with_seed(quirks: Quirks, seed: u64) -> Self {
+        let mut chip8 = Self::new(quirks);
+        chip8.set_seed(seed);
         chip8
     }
 
+    /// A function to restart the CXNN random numbers from `seed`
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+    }
+
+    /// A function to get the seed of the random numbers, `None` when they are unpredictable
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// A function to create a CPU with `memory_size` bytes of memory instead of the default 4 KB,
+    /// e.g. 64 KB for XO-CHIP. Panics if the size cannot hold the program area or is above 64 KB.
+    pub fn with_memory_size(quirks: Quirks, memory_size: usize) -> Self {
+        assert!(
+            (PROGRAM_START..=MAX_MEMORY_SIZE).contains(&memory_size),
+            "memory size must be between {} and {} bytes, got {}",
+            PROGRAM_START,
+            MAX_MEMORY_SIZE,
+            memory_size
+        );
+        let mut chip8 = Chip8 {
+            quirks,
+            memory: vec![0; memory_size],
+            ..Default::default()
+        };
+
+        chip8.load_fonts();
+        chip8
+    }
+
+    /// A function to change the address where ROMs are loaded and execution starts,
+    /// e.g. 0x600 for ETI-660 programs. Call it before `load_rom`.
+    /// Panics if the address is outside of memory.
+    pub fn set_program_start(&mut self, addr: u16) {
+        assert!(
+            (addr as usize) < self.memory.len(),
+            "program start {:#05x} is outside of memory",
+            addr
+        );
+        self.program_start = addr;
+        self.program_counter = addr;
+    }
+
+    /// A function to get the address where ROMs are loaded and execution starts
+    pub fn program_start(&self) -> u16 {
+        self.program_start
+    }
+
+    /// A function to get the size of the memory in bytes
+    pub fn memory_size(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// A function to load the font data into memory at 0x050
+    fn load_fonts(&mut self) {
+        self.memory[FONT_START..FONT_START + FONT_SIZE].copy_from_slice(&FONTS);
+    }
+
+    /// A function to restart the CPU: memory, display, registers, stack and timers are cleared
+    /// while the configuration (quirks, clock speed, debugging...) is kept.
+    /// With `keep_rom` the last loaded ROM is loaded again so the same game restarts.
+    pub fn reset(&mut self, keep_rom: bool) {
+        self.memory.fill(0);
+        self.load_fonts();
+        self.display = Framebuffer::new();
+        self.display_dirty = true;
+        self.hi_res = false;
+        self.planes = PLANE_1;
+        self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        self.pitch = DEFAULT_PITCH;
+        self.program_counter = self.program_start;
+        self.index_register = 0;
+        self.stack = [0; STACK_SIZE];
+        self.stack_pointer = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.registers = [0; NUM_REGISTERS];
+        self.keypad = [false; NUM_KEYS];
+        self.last_timer_update = None;
+        self.break_requested = false;
+        self.stopped_at = None;
+        self.waiting_for_vblank = false;
+        self.pressed_key = None;
+        self.waiting_for_key = false;
+        self.key_presses.clear();
+        self.halted = false;
+        self.steps_until_break = None;
+        self.run_to_address = None;
+        if let Some(seed) = self.seed {
+            self.rng = ChaCha12Rng::seed_from_u64(seed); // replay the same random numbers
+        }
+        #[cfg(feature = "serde")]
+        self.history.clear();
+
+        let rom = std::mem::take(&mut self.rom);
+        if keep_rom {
+            self.load_rom(rom)
+                .expect("the ROM fitted in memory when it was first loaded");
+        }
+    }
+
     /// A function to load the ROM into memory
-    pub fn load_rom(&mut self, rom: Vec<u8>) {
-        for (i, byte) in rom.iter().enumerate() {
-            self.memory[self.program_counter as usize + i] = *byte
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), LoadError> {
+        self.copy_rom(&rom)?;
+        self.rom = rom;
+        Ok(())
+    }
+
+    /// A function to load the ROM into memory from borrowed bytes, e.g. from `include_bytes!`.
+    /// The bytes are copied into the buffer kept for `reset`, reusing its allocation.
+    pub fn load_rom_slice(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        self.copy_rom(rom)?;
+        self.rom.clear();
+        self.rom.extend_from_slice(rom);
+        Ok(())
+    }
+
+    /// Function to copy the ROM into memory at the program start address
+    fn copy_rom(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        self.load_at(self.program_start, rom)
+    }
+
+    /// A function to write `data` into memory at `addr`, e.g. a data blob expected by a ROM
+    /// or a test fixture. Unlike a ROM, the data is not loaded again by `reset`.
+    pub fn load_at(&mut self, addr: u16, data: &[u8]) -> Result<(), LoadError> {
+        let start = addr as usize;
+        let available = self.memory.len().saturating_sub(start);
+        if data.len() > available {
+            return Err(LoadError::RomTooLarge {
+                size: data.len(),
+                available,
+            });
         }
+
+        self.memory[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// A function to get the value of register V`index` (panics if `index` is above 0xF)
+    pub fn register(&self, index: usize) -> u8 {
+        self.registers[index]
+    }
+
+    /// A function to get all the general-purpose registers V0-VF
+    pub fn registers(&self) -> &[u8; NUM_REGISTERS] {
+        &self.registers
+    }
+
+    /// A function to get the program counter (PC)
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    /// A function to get the index register (I)
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    /// A function to get the stack pointer, i.e. the number of nested subroutine calls
+    pub fn stack_pointer(&self) -> usize {
+        self.stack_pointer
+    }
+
+    /// A function to get the compatibility quirks in use
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// A function to get the emulation speed in instructions per second
+    pub fn clock_speed(&self) -> u64 {
+        self.clock_speed
+    }
+
+    /// A function to set the emulation speed in instructions per second
+    pub fn set_clock_speed(&mut self, hz: u64) {
+        self.clock_speed = hz.max(1); // at least one instruction per second
+    }
+
+    /// A function to get the number of times per second the timers are decremented
+    pub fn timer_frequency(&self) -> u64 {
+        self.timer_frequency
+    }
+
+    /// A function to change how many times per second `update_timers` decrements the timers
+    pub fn set_timer_frequency(&mut self, hz: u64) {
+        self.timer_frequency = hz.max(1); // at least once per second
+    }
+
+    /// Function to get the time between two timer decrements, 1/60 s by default
+    fn timer_interval(&self) -> Duration {
+        Duration::from_micros(1_000_000 / self.timer_frequency)
+    }
+
+    /// A function to select how sprites are drawn by the DXYN opcode
+    pub fn set_draw_mode(&mut self, draw_mode: DrawMode) {
+        self.draw_mode = draw_mode;
+    }
+
+    /// A function to subscribe to a description of every executed instruction.
+    /// The hook receives the address of the instruction, the raw opcode and a human
+    /// readable description.
+    pub fn set_trace_hook(&mut self, hook: TraceHook) {
+        self.trace_hook = Some(hook);
+    }
+
+    /// A function to subscribe to a description of every DXYN draw: the sprite bytes,
+    /// where it was drawn, how many pixels it set and cleared, and whether VF was set
+    pub fn set_draw_trace_hook(&mut self, hook: DrawTraceHook) {
+        self.draw_trace_hook = Some(hook);
+    }
+
+    /// A function to enable or disable the debugging features
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    /// A function to set the magic opcode ROM authors can embed as an in-code breakpoint.
+    /// The opcode is always treated as a no-op, and only requests a break in debug mode.
+    pub fn set_break_opcode(&mut self, break_opcode: Option<u16>) {
+        self.break_opcode = break_opcode;
+    }
+
+    /// A function to break again after executing `steps` instructions.
+    /// Zero steps breaks straight away without executing anything.
+    pub fn break_after(&mut self, steps: usize) {
+        if steps == 0 {
+            self.break_requested = true;
+        } else {
+            self.steps_until_break = Some(steps);
+        }
+    }
+
+    /// A function to break the next time the program counter reaches `addr`.
+    /// At least one instruction is executed first, so running to the current
+    /// address runs until the program comes back to it.
+    pub fn run_to(&mut self, addr: u16) {
+        self.run_to_address = Some(addr);
+    }
+
+    /// A function to break whenever the program counter reaches `addr`, before the
+    /// instruction there is executed. Unlike `run_to` the breakpoint stays until removed.
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// A function to remove the breakpoint at `addr`, if there is one
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// A function to get the addresses of the breakpoints, in increasing order
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// A function to check if the next instruction is at a breakpoint, e.g. as the predicate
+    /// of `run_until` to run headlessly up to a breakpoint
+    pub fn is_at_breakpoint(&self) -> bool {
+        self.breakpoints.contains(&self.program_counter)
+    }
+
+    /// A function to break whenever an instruction reads or writes memory at `addr`,
+    /// including instruction fetches, sprite reads and FX33/FX55/FX65.
+    /// While any watchpoint is set, every memory access of an instruction is looked up
+    /// in the set of watchpoints, which makes emulation noticeably slower.
+    pub fn add_watchpoint(&mut self, addr: u16) {
+        self.watchpoints.insert(addr);
+    }
+
+    /// A function to remove the watchpoint at `addr`, if there is one
+    pub fn remove_watchpoint(&mut self, addr: u16) {
+        self.watchpoints.remove(&addr);
+    }
+
+    /// A function to get the addresses of the watchpoints, in increasing order
+    pub fn watchpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.watchpoints.iter().copied()
+    }
+
+    /// A function to take the watchpoint accesses made since the last call, oldest first
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.watch_hits)
+    }
+
+    /// A function to check, and clear, a pending break request.
+    /// A breakpoint at the address the break was taken at does not break again before
+    /// the instruction there has run.
+    pub fn take_break_request(&mut self) -> bool {
+        let requested = std::mem::take(&mut self.break_requested);
+        if requested {
+            self.stopped_at = Some(self.program_counter);
+        }
+        requested
+    }
+
+    /// A function to get the total number of instructions executed since the CPU was created
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    /// A function to get the number of executed instructions per opcode category
+    pub fn opcode_histogram(&self) -> &[u64; OPCODE_CATEGORIES] {
+        &self.opcode_histogram
+    }
+
+    /// A function to add previously recorded counts to the opcode histogram.
+    /// Counts stop at `u64::MAX` rather than overflowing, e.g. with a tampered stats file.
+    pub fn merge_opcode_histogram(&mut self, counts: &[u64; OPCODE_CATEGORIES]) {
+        for (count, extra) in self.opcode_histogram.iter_mut().zip(counts) {
+            *count = count.saturating_add(*extra);
+        }
+    }
+
+    /// A function to check if the 128x64 high resolution mode is active
+    pub fn hi_res(&self) -> bool {
+        self.hi_res
+    }
+
+    /// A function to switch between the 64x32 and 128x64 resolutions
+    fn set_hi_res(&mut self, hi_res: bool) {
+        self.hi_res = hi_res;
+        self.display.set_hi_res(hi_res);
+        self.display_dirty = true;
+    }
+
+    /// A function to get the current display buffer
+    pub fn display_buffer(&self) -> &Framebuffer {
+        &self.display
+    }
+
+    /// A function to get the display as one byte per pixel (0 when off, 255 when lit),
+    /// row by row, for renderers that do not use `Framebuffer`
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let (width, height) = self.dimensions();
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(if self.display.pixel(x, y) { 255 } else { 0 });
+            }
+        }
+        pixels
+    }
+
+    /// A function to get the display as text, `#` for a lit pixel and a space for an
+    /// unlit one, with a newline between rows, e.g. to compare screens in tests
+    pub fn display_to_string(&self) -> String {
+        let (width, height) = self.dimensions();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| if self.display.pixel(x, y) { '#' } else { ' ' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// A function to turn a pixel on or off directly, without XOR and without touching VF,
+    /// e.g. for debug tools annotating the screen. Pixels outside of the active resolution
+    /// are ignored. Sprites drawn by the ROM can still erase the pixel afterwards.
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let (width, height) = self.dimensions();
+        if x < width && y < height {
+            self.display.set_pixel(x, y, on);
+            self.display_dirty = true;
+        }
+    }
+
+    /// A function to get the active display resolution as `(width, height)`
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.display.width(), self.display.height())
+    }
+
+    /// A function to check if the display changed since the last call, clearing the flag.
+    /// Frontends can skip redrawing while this returns `false`.
+    pub fn take_display_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.display_dirty)
+    }
+
+    /// A function to get how many sprite pixels landed on already lit pixels during the
+    /// last DXYN, i.e. how many pixels were erased in XOR mode. VF only tells if there was any.
+    pub fn last_collision_count(&self) -> u32 {
+        self.last_collision_count
+    }
+
+    /// A function to replace the whole display buffer, e.g. to restore a saved screen
+    pub fn set_display_buffer(&mut self, buffer: Framebuffer) {
+        self.display = buffer;
+        self.display_dirty = true;
+    }
+
+    /// A function to view the whole memory, e.g. for a memory inspector
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// A function to read (peek) a single byte from memory
+    pub fn read_memory(&self, addr: u16) -> Result<u8, Chip8Error> {
+        self.memory
+            .get(addr as usize)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds(addr))
+    }
+
+    /// A function to write (poke) a single byte into memory.
+    /// This is a debugging tool: it bypasses the normal instruction flow,
+    /// so writing into a running program can leave the emulation in a state
+    /// the original hardware would never reach.
+    pub fn write_memory(&mut self, addr: u16, byte: u8) -> Result<(), Chip8Error> {
+        let cell = self
+            .memory
+            .get_mut(addr as usize)
+            .ok_or(Chip8Error::MemoryOutOfBounds(addr))?;
+        *cell = byte;
+        Ok(())
     }
 
     /// A function to decrement the times.
     /// If the values of the timer is above zero,
-    /// it should be decremented by one 60 times per second
+    /// it should be decremented by one 60 times per second.
+    /// It reads the wall clock, which panics on wasm32-unknown-unknown:
+    /// frontends without a clock call `tick_timers` instead.
     pub fn update_timers(&mut self) {
-        let elapsed_time = self.last_timer_update.elapsed();
+        let now = Instant::now();
+        // the timers start counting at the first call
+        let last_timer_update = *self.last_timer_update.get_or_insert(now);
 
         // check if enough time has passed to decrement timer (60Hz)
-        if elapsed_time > TIMER_INTERVAL {
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
-            }
-
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1;
-            }
+        if now.duration_since(last_timer_update) > self.timer_interval() {
+            self.tick_timers();
 
             // update the mast timer update time to now
-            self.last_timer_update = Instant::now(); // there is a trivial delay here
+            self.last_timer_update = Some(now);
         }
     }
 
-    pub fn update_sound(
-        &mut self,
-        audio_device: &AudioDevice<SquareWave>,
-        is_playing: &Arc<Mutex<bool>>,
-    ) {
+    /// A function to decrement the non-zero timers once, as happens at each 60 Hz vertical blank.
+    /// Unlike `update_timers` it does not look at the clock, so frame-based frontends and
+    /// tests can drive the timers deterministically by calling it 60 times per second.
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
         if self.sound_timer > 0 {
-            // Start playing sound if not already playing
-            let mut playing = is_playing.lock().unwrap();
-            if !*playing {
-                audio_device.resume();
-                *playing = true;
+            self.sound_timer -= 1;
+        }
+
+        // the vertical blank ends the wait of the display_wait quirk
+        self.waiting_for_vblank = false;
+    }
+
+    /// A function to run one 60 Hz frame for frame-based frontends: execute `cycles`
+    /// instructions, then decrement the timers once.
+    /// Returns whether the display changed during the frame.
+    pub fn run_frame(&mut self, cycles: usize) -> Result<bool, Chip8Error> {
+        let before = self.display;
+        for _ in 0..cycles {
+            self.cycle()?;
+        }
+        self.tick_timers();
+        Ok(self.display != before)
+    }
+
+    /// A function to execute instructions until `predicate` holds, giving up after `max_cycles`
+    /// instructions or when the ROM halts in an infinite loop, so that a buggy program cannot
+    /// run forever. Timers are left alone, as with `step`.
+    /// Returns whether the predicate was met.
+    pub fn run_until(
+        &mut self,
+        max_cycles: usize,
+        predicate: impl Fn(&Chip8) -> bool,
+    ) -> Result<bool, Chip8Error> {
+        for _ in 0..max_cycles {
+            if predicate(self) {
+                return Ok(true);
             }
-        } else {
-            // Stop playing sound if timer reaches 0
-            let mut playing = is_playing.lock().unwrap();
-            if *playing {
-                audio_device.pause();
-                *playing = false;
+            if self.halted {
+                return Ok(false);
+            }
+            self.step()?;
+        }
+        Ok(predicate(self))
+    }
+
+    /// A function to press or release key `key` of the keypad, keys above 0xF are ignored
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        let Some(state) = self.keypad.get_mut(key as usize) else {
+            return;
+        };
+        if pressed && !*state {
+            // remember the order of the presses so FX0A picks the earliest one
+            self.key_presses.retain(|&queued| queued != key);
+            self.key_presses.push_back(key);
+        }
+        if *state != pressed {
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(self.instruction_count - self.input_start, key, pressed);
+            }
+        }
+        *state = pressed;
+    }
+
+    /// A function to start logging the keypad input, to replay the session later with `start_replay`.
+    /// The random numbers are seeded if they were not already, so that they can be replayed too.
+    pub fn start_recording(&mut self) {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        self.set_seed(seed);
+        self.recorder = Some(Recorder::new(Some(seed)));
+        self.input_start = self.instruction_count;
+    }
+
+    /// A function to stop logging the keypad input, returning what was recorded
+    pub fn stop_recording(&mut self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+
+    /// A function to play back a recording: its seed is restored and its key changes
+    /// are applied after the same number of instructions as when they were recorded.
+    /// Start it right after loading the ROM the recording was made with.
+    pub fn start_replay(&mut self, recording: &Recorder) {
+        if let Some(seed) = recording.seed() {
+            self.set_seed(seed);
+        }
+        self.replay = recording.events().iter().copied().collect();
+        self.input_start = self.instruction_count;
+    }
+
+    /// A function to check if recorded input is still being replayed
+    pub fn is_replaying(&self) -> bool {
+        !self.replay.is_empty()
+    }
+
+    /// Function to apply the replayed key changes that are due before the next instruction
+    fn replay_input(&mut self) {
+        let cycle = self.instruction_count - self.input_start;
+        while let Some(event) = self.replay.front().copied() {
+            if event.cycle > cycle {
+                break;
             }
+            self.replay.pop_front();
+            self.set_key(event.key, event.pressed);
         }
     }
 
-    /// Fetch the instruction from memory at the current program counter
-    pub fn fetch(&mut self) -> u16 {
+    /// A function to check if the CPU is stopped at FX0A until a key is pressed and released,
+    /// e.g. to show a "press any key" hint
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key
+    }
+
+    /// A function to check if key `key` of the keypad is pressed, keys above 0xF never are
+    pub fn key_state(&self, key: u8) -> bool {
+        self.keypad.get(key as usize).copied().unwrap_or(false)
+    }
+
+    /// A function to check if the ROM is stuck in a jump to itself, so it has nothing left to do
+    /// and the caller can stop cycling
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// A function to get the current value of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// A function to get the current value of the sound timer, the beep plays while it is non-zero
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// A function to check if the beep should be playing
+    pub fn is_beeping(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// A function to get the XO-CHIP audio pattern, 128 1-bit samples played while beeping
+    pub fn audio_pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        &self.audio_pattern
+    }
+
+    /// A function to get the XO-CHIP pitch of the audio pattern
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// A function to get the number of audio pattern bits played per second,
+    /// 4000 at the default pitch and doubling every 48 steps of pitch
+    pub fn audio_playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - DEFAULT_PITCH as f32) / 48.0)
+    }
+
+    /// Fetch the instruction from memory at the current program counter.
+    /// Fails instead of panicking when the program counter ran off the end of memory.
+    pub fn fetch(&mut self) -> Result<u16, Chip8Error> {
+        let opcode = self.peek_opcode()?;
+
+        // increment program counter by 2, wrapping at the end of a 64 KB memory
+        self.program_counter = self.program_counter.wrapping_add(2);
+
+        Ok(opcode)
+    }
+
+    /// A function to read the instruction at the program counter without executing it
+    /// or moving the program counter, e.g. to show the next instruction in a debugger
+    pub fn peek_opcode(&self) -> Result<u16, Chip8Error> {
         // An instruction is two successive bytes that is combined to 16-bit instruction
         let pc = self.program_counter as usize;
+        if pc + 1 >= self.memory.len() {
+            return Err(Chip8Error::ProgramCounterOutOfBounds(self.program_counter));
+        }
         let op_byte1 = self.memory[pc] as u16;
         let op_byte2 = self.memory[pc + 1] as u16;
 
-        // increment program counter by 2
-        self.program_counter += 2;
-
         // combine the two bytes into a single 16 bit output
-        op_byte1 << 8 | op_byte2
+        Ok(op_byte1 << 8 | op_byte2)
     }
 
-    /// Decode the instruction to find out what the emulator should do
-    pub fn decode(&mut self, &opcode: &u16) -> (u8, u8, u8, u8, u8, u16) {
-        // Opcode decoding
-        // the first `nibble` is the category of instruction
-        // Mask off using a binary AND, then shift 12 places and truncate to u8.
-        let c = ((opcode & 0xF000) >> 12) as u8; // first nibble (operation category)
-        let x = ((opcode & 0x0F00) >> 8) as u8; // second nibble (register loop up)
-        let y = ((opcode & 0x00F0) >> 4) as u8; // third nibble (register look up)
-        let n = (opcode & 0x000F) as u8; // fourth nibble (a 4-bit number)
-        let nn = (opcode & 0x00FF) as u8; // second byte (an 8-bit immediate number)
-        let nnn = opcode & 0x0FFF; // second, third and fourth nibbles (a 12 bit immediate memory address)
+    /// Decode the instruction to find out what the emulator should do.
+    /// The nibbles are extracted by `OpCode::decode`, the single place doing it.
+    pub fn decode(&self, &opcode: &u16) -> OpCode {
+        OpCode::decode(&opcode)
+    }
+
+    /// A function to describe the CPU state (registers, PC, I, SP, timers and the
+    /// top of the stack) as a readable multi-line string for debugging
+    pub fn dump_state(&self) -> String {
+        let mut dump = String::new();
+        for (row, registers) in self.registers.chunks(4).enumerate() {
+            let line: Vec<String> = registers
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("V{:X}: {:#04x}", row * 4 + i, value))
+                .collect();
+            dump.push_str(&line.join("  "));
+            dump.push('\n');
+        }
+        dump.push_str(&format!(
+            "PC: {:#05x}  I: {:#05x}  SP: {}\n",
+            self.program_counter, self.index_register, self.stack_pointer
+        ));
+        dump.push_str(&format!(
+            "DT: {}  ST: {}\n",
+            self.delay_timer, self.sound_timer
+        ));
 
-        (c, x, y, n, nn, nnn)
+        // most recent return addresses first
+        let top: Vec<String> = self.stack[..self.stack_pointer]
+            .iter()
+            .rev()
+            .take(STACK_DUMP_DEPTH)
+            .map(|addr| format!("{:#05x}", addr))
+            .collect();
+        dump.push_str(&format!("Stack: [{}]", top.join(", ")));
+        if self.stack_pointer > STACK_DUMP_DEPTH {
+            dump.push_str(&format!(" +{} more", self.stack_pointer - STACK_DUMP_DEPTH));
+        }
+        dump
     }
 
-    /// A function to Run the Chip-8 CPU
-    pub fn cycle(&mut self) {
-        // Run emulator here.
+    /// A function to get the time left before the timers are next decremented by `update_timers`
+    pub fn time_until_timer_tick(&self) -> Duration {
+        match self.last_timer_update {
+            Some(last_timer_update) => self
+                .timer_interval()
+                .saturating_sub(last_timer_update.elapsed()),
+            None => self.timer_interval(), // the timers have not started counting yet
+        }
+    }
+
+    /// A function to Run the Chip-8 CPU.
+    /// Returns `true` when a sprite was drawn, so that with the display_wait quirk
+    /// the caller can sleep until the next timer tick instead of spinning.
+    /// While waiting for the vertical blank no instruction is executed.
+    pub fn cycle(&mut self) -> Result<bool, Chip8Error> {
+        if self.waiting_for_vblank {
+            return Ok(false);
+        }
+        #[cfg(feature = "serde")]
+        self.record_history();
+        let executed = self.step()?;
+        Ok(executed.is_some_and(|executed| executed.c == 0xD))
+    }
+
+    /// A function to execute exactly one instruction, returning the instruction that was executed.
+    /// When the program counter is at a breakpoint that was not stopped at yet, e.g. one at the
+    /// program start, a break is requested instead and `None` is returned without executing
+    /// anything: the instruction runs on the next call.
+    pub fn step(&mut self) -> Result<Option<OpCode>, Chip8Error> {
+        if self.is_at_breakpoint() && self.stopped_at != Some(self.program_counter) {
+            self.stopped_at = Some(self.program_counter);
+            self.break_requested = true;
+            return Ok(None);
+        }
+        self.stopped_at = None;
+
+        self.replay_input();
+
         // get and decode opcode
-        let opcode = self.fetch();
-        let (c, x, y, n, nn, nnn) = self.decode(&opcode);
+        let address = self.program_counter; // address of the instruction, for the trace and watchpoints
+        let opcode = self.fetch()?;
+        self.watch(address, address as usize, 2, MemoryAccess::Read);
+        let decoded = self.decode(&opcode);
+        let count = &mut self.opcode_histogram[decoded.c as usize];
+        *count = count.saturating_add(1);
+        self.instruction_count += 1;
+        if self.break_opcode == Some(opcode) {
+            // In-code breakpoint: a no-op that only signals a break in debug mode,
+            // keeping any break already requested, e.g. by a watchpoint on the fetch
+            self.break_requested |= self.debug;
+            self.check_debug_targets();
+            return Ok(Some(decoded));
+        }
+        let OpCode {
+            c,
+            x,
+            y,
+            n,
+            nn,
+            nnn,
+        } = decoded;
 
         let vx = self.registers[x as usize]; // value at x in the register
         let vy = self.registers[y as usize]; // value at y in the register
@@ -183,255 +951,425 @@ impl Chip8 {
                     (0, 0, 0) => {}
                     (0, 0xE, 0) => {
                         // 0x00E0: Clear screen
-                        println!("Handling opcode: {:#x?} - clearing display", opcode);
-                        self.display = [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+                        trace!(self, address, opcode, "clearing display");
+                        self.display.clear_planes(self.planes);
+                        self.display_dirty = true;
+                    }
+                    (0, 0xC, n) => {
+                        // 0x00CN: scroll display down N pixels (SUPER-CHIP)
+                        trace!(self, address, opcode, "scrolling display down {} pixels", n);
+                        self.display.scroll_down(n as usize, self.planes);
+                        self.display_dirty = true;
+                    }
+                    (0, 0xF, 0xB) => {
+                        // 0x00FB: scroll display right 4 pixels (SUPER-CHIP)
+                        trace!(self, address, opcode, "scrolling display right");
+                        self.display.scroll_right(4, self.planes);
+                        self.display_dirty = true;
+                    }
+                    (0, 0xF, 0xC) => {
+                        // 0x00FC: scroll display left 4 pixels (SUPER-CHIP)
+                        trace!(self, address, opcode, "scrolling display left");
+                        self.display.scroll_left(4, self.planes);
+                        self.display_dirty = true;
+                    }
+                    (0, 0xF, 0xE) => {
+                        // 0x00FE: disable high resolution mode (SUPER-CHIP)
+                        trace!(self, address, opcode, "switching to low resolution");
+                        self.set_hi_res(false);
+                    }
+                    (0, 0xF, 0xF) => {
+                        // 0x00FF: enable 128x64 high resolution mode (SUPER-CHIP)
+                        trace!(self, address, opcode, "switching to high resolution");
+                        self.set_hi_res(true);
                     }
                     (0, 0xE, 0xE) => {
                         // 0x00EE: return subroutine
-                        println!("Handling opcode: {:#x?} - return subroutine", opcode);
-                        self.return_subroutine();
+                        trace!(self, address, opcode, "return subroutine");
+                        self.return_subroutine()?;
                     }
-                    _ => panic!("Unimplemented opcode: {:#x?}", opcode),
+                    // 0x0NNN: call a machine code routine of the COSMAC VIP's 1802 CPU
+                    _ => return Err(Chip8Error::MachineCodeCallUnsupported(nnn)),
                 }
             }
             0x1 => {
                 // 0x1NNN: Jump to NNN address
-                println!(
-                    "Handling opcode: {:#x?} - setting program counter to {}",
-                    opcode, nnn
-                );
+                trace!(self, address, opcode, "setting program counter to {}", nnn);
+                // ROMs commonly end by jumping to the jump itself
+                self.halted = nnn == self.program_counter.wrapping_sub(2);
                 self.program_counter = nnn;
             }
             0x2 => {
                 // 0x2NNN: call_subroutine subroutine at nnn
-                println!(
-                    "Handling opcode: {:#x?} - call subroutine at {:#x?}",
-                    opcode, nnn
-                );
-                self.call_subroutine(nnn);
+                trace!(self, address, opcode, "call subroutine at {:#x?}", nnn);
+                self.call_subroutine(nnn)?;
             }
             0x3 => {
                 // 0x3XNN: skip conditionally
-                println!(
-                    "Handling opcode: {:#x?} - skip one if VX({}) == NN({})",
-                    opcode, vx, nn
+                trace!(
+                    self,
+                    address,
+                    opcode,
+                    "skip one if VX({}) == NN({})",
+                    vx,
+                    nn
                 );
                 if vx == nn {
-                    self.program_counter += 2;
+                    self.skip_instruction();
                 }
             }
             0x4 => {
                 // 0x4XNN: skip conditionally
-                println!(
-                    "Handling opcode: {:#x?} - skip one if VX({}) != NN({})",
-                    opcode, vx, nn
+                trace!(
+                    self,
+                    address,
+                    opcode,
+                    "skip one if VX({}) != NN({})",
+                    vx,
+                    nn
                 );
                 if vx != nn {
-                    self.program_counter += 2;
+                    self.skip_instruction();
                 }
             }
-            0x5 => {
+            0x5 if n == 0 => {
                 // 0x5XY0: skip conditionally
-                println!(
-                    "Handling opcode: {:#x?} - skip one if VX({}) == VY({})",
-                    opcode, vx, vy
+                trace!(
+                    self,
+                    address,
+                    opcode,
+                    "skip one if VX({}) == VY({})",
+                    vx,
+                    vy
                 );
                 if vx == vy {
-                    self.program_counter += 2;
+                    self.skip_instruction();
+                }
+            }
+            0x5 if (n == 0x2 || n == 0x3) && self.quirks.xo_chip => {
+                // 0x5XY2 / 0x5XY3: save / load VX..VY at I, in reverse order when X > Y (XO-CHIP)
+                let registers: Vec<usize> = if x <= y {
+                    (x..=y).map(usize::from).collect()
+                } else {
+                    (y..=x).rev().map(usize::from).collect()
+                };
+                let start = self.index_register as usize;
+                if start + registers.len() > self.memory.len() {
+                    return Err(Chip8Error::MemoryOutOfBounds(self.index_register));
+                }
+                let access = if n == 0x2 {
+                    MemoryAccess::Write
+                } else {
+                    MemoryAccess::Read
+                };
+                self.watch(address, start, registers.len(), access);
+                if n == 0x2 {
+                    trace!(self, address, opcode, "saving v{}..v{} to memory", x, y);
+                    for (offset, register) in registers.into_iter().enumerate() {
+                        self.memory[start + offset] = self.registers[register];
+                    }
+                } else {
+                    trace!(self, address, opcode, "loading v{}..v{} from memory", x, y);
+                    for (offset, register) in registers.into_iter().enumerate() {
+                        self.registers[register] = self.memory[start + offset];
+                    }
                 }
             }
             0x6 => {
                 // 6XNN: Set VX to NN
-                println!(
-                    "Handling opcode: {:#x?} - setting v{} register to {}",
-                    opcode, x, nn
-                );
+                trace!(self, address, opcode, "setting v{} register to {}", x, nn);
                 self.registers[x as usize] = nn;
             }
             0x7 => {
                 // 7XNN: Add value to register VX
-                println!(
-                    "Handling opcode: {:#x?} - adding {} to v{} register",
-                    opcode, nn, x
-                );
+                trace!(self, address, opcode, "adding {} to v{} register", nn, x);
                 self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn);
             }
             0x8 => {
                 match n {
                     0x0 => {
                         // 0x8XY0: Set
-                        println!(
-                            "Handling opcode: {:#x?} - setting v{} to v{}",
-                            opcode, vx, vy
-                        );
+                        trace!(self, address, opcode, "setting v{} to v{}", x, y);
                         self.registers[x as usize] = self.registers[y as usize];
                     }
                     0x1 => {
                         // 0x8XY1: Binary OR
-                        println!("Handling opcode: {:#x?} - setting  v{} to binary OR of v{} and v{} register", opcode, x, x, y);
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "setting v{} to binary OR of v{} and v{} register",
+                            x,
+                            x,
+                            y
+                        );
                         self.registers[x as usize] = vx | vy;
+                        self.reset_vf();
                     }
                     0x2 => {
                         // 0x8XY2: Binary AND
-                        println!("Handling opcode: {:#x?} - setting  v{} to binary AND of v{} and v{} register", opcode, x, x, y);
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "setting v{} to binary AND of v{} and v{} register",
+                            x,
+                            x,
+                            y
+                        );
                         self.registers[x as usize] = vx & vy;
+                        self.reset_vf();
                     }
                     0x3 => {
                         // 0x8XY3: Logical XOR
-                        println!("Handling opcode: {:#x?} - setting  v{} to logical XOR of v{} and v{} register", opcode, x, x, y);
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "setting v{} to logical XOR of v{} and v{} register",
+                            x,
+                            x,
+                            y
+                        );
                         self.registers[x as usize] = vx ^ vy;
+                        self.reset_vf();
                     }
                     0x4 => {
                         // 0x8XY4: Add overflowing
-                        println!("Handling opcode: {:#x?} - setting v{} to the sum of v{} and v{} register", opcode, x, x, y);
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "setting v{} to the sum of v{} and v{} register",
+                            x,
+                            x,
+                            y
+                        );
                         self.add_xy(x, y);
                     }
                     0x5 => {
                         // 0x8XY5: VX - VY
-                        println!("Handling opcode: {:#x?} - setting v{} to the diff of v{} and v{} register", opcode, x, x, y);
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "setting v{} to the diff of v{} and v{} register",
+                            x,
+                            x,
+                            y
+                        );
                         self.subtract_xy(x, y);
                     }
                     0x7 => {
                         // 0x8XY5: VY - VX
-                        println!("Handling opcode: {:#x?} - setting v{} to the diff of v{} and v{} register", opcode, x, y, x);
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "setting v{} to the diff of v{} and v{} register",
+                            x,
+                            y,
+                            x
+                        );
                         self.subtract_yx(x, y);
                     }
                     0x6 => {
                         // 0x8XY6: Shift Right
-                        println!("Handling opcode: {:#x?} - shifting v{} >> 1", opcode, x);
-                        if !self.modern {
-                            // set VX to the value of VY
-                            self.registers[x as usize] = self.registers[y as usize]
-                            // Set VX to the value of VY
-                        }
-                        let vx_pre_shift = self.registers[x as usize]; // value of vx before the shift operation
-                        self.registers[x as usize] >>= 1; // Shift VX one bit to the right
-
-                        self.registers[0xF] = if vx_pre_shift & 0b0000_0001 != 0 {
-                            1
-                        } else {
-                            0
-                        };
-                        // set register values
+                        trace!(self, address, opcode, "shifting v{} >> 1", x);
+                        self.shift_right(x, y);
                     }
                     0xE => {
                         // 0x8XYE: Shift Left
-                        println!("Handling opcode: {:#x?} - shifting v{} << 1", opcode, x);
-                        if !self.modern {
-                            // set VX to the value of VY
-                            self.registers[x as usize] = self.registers[y as usize];
-                            // Set VX to the value of VY
-                        }
-                        let vx_pre_shift = self.registers[x as usize]; // value of vx before the shift operation
-                        self.registers[x as usize] <<= 1; // Shift VX one bit to the left
-
-                        self.registers[0xF] = if vx_pre_shift & 0b1000_0000 != 0 {
-                            1
-                        } else {
-                            0
-                        }; // set register values
+                        trace!(self, address, opcode, "shifting v{} << 1", x);
+                        self.shift_left(x, y);
                     }
-                    _ => panic!("Unimplemented opcode: {:#x?}", opcode),
+                    _ => return Err(Chip8Error::UnknownOpcode(opcode)),
                 }
             }
-            0x9 => {
+            0x9 if n == 0 => {
                 // 0x9XY0: skip conditionally
-                println!(
-                    "Handling opcode: {:#x?} - skip one if VX({}) =! VY({})",
-                    opcode, vx, vy
+                trace!(
+                    self,
+                    address,
+                    opcode,
+                    "skip one if VX({}) != VY({})",
+                    vx,
+                    vy
                 );
                 if vx != vy {
-                    self.program_counter += 2;
+                    self.skip_instruction();
                 }
             }
             0xA => {
                 // ANNN: Set index register I to NNN
-                println!(
-                    "Handling opcode: {:#x?} - setting index register to {}",
-                    opcode, nnn
-                );
+                trace!(self, address, opcode, "setting index register to {}", nnn);
                 self.index_register = nnn;
             }
             0xB => {
                 // 0xBNNN: Jump with offset
-                // TODO: add support for "qurik" configuration
-                println!(
-                    "Handling opcode: {:#x?} - jump to address {} + {}",
-                    opcode, nnn, self.registers[0]
+                // SUPER-CHIP reads it as 0xBXNN and offsets with VX instead of V0
+                let offset_register = if self.quirks.jump_uses_vx { x } else { 0 };
+                let offset = self.registers[offset_register as usize];
+                trace!(
+                    self,
+                    address,
+                    opcode,
+                    "jump to address {} + {}",
+                    nnn,
+                    offset
                 );
-                self.program_counter = nnn + self.registers[0] as u16;
+                self.program_counter = nnn + offset as u16;
             }
             0xC => {
                 // OxCXNN: Random
-                let rand_num: u8 = rand::rng().random();
+                trace!(
+                    self,
+                    address,
+                    opcode,
+                    "setting v{} to a random number AND {}",
+                    x,
+                    nn
+                );
+                let rand_num: u8 = self.rng.random();
                 self.registers[x as usize] = nn & rand_num;
             }
             0xD => {
                 // DXYN: draw
-                println!(
-                    "Handling opcode: {:#x?}. drawing sprite of {} rows at ({}, {})",
-                    opcode, n, x, y
+                trace!(
+                    self,
+                    address,
+                    opcode,
+                    "drawing sprite of {} rows at ({}, {})",
+                    n,
+                    x,
+                    y
                 );
                 // N = height of the sprite
                 // X = horizontal coordinate in VX
                 // Y = vertical coordinate in VY
-                let x_start = vx % DISPLAY_WIDTH as u8; // X coordinate
-                let y_start = vy % DISPLAY_HEIGHT as u8; // Y coordinate
+                let x_start = vx as usize % self.display.width(); // X coordinate
+                let y_start = vy as usize % self.display.height(); // Y coordinate
+                let mut pixels_set = 0; // number of pixels turned on by the sprite
+                let mut pixels_cleared = 0; // number of pixels turned off by the sprite
+                let mut collisions = 0; // number of sprite pixels landing on lit pixels
+
+                // SUPER-CHIP in high resolution and XO-CHIP: DXY0 draws a 16x16 sprite
+                // made of two bytes per row
+                let (sprite_width, sprite_height) =
+                    if n == 0 && (self.hi_res || self.quirks.xo_chip) {
+                        (16, 16)
+                    } else {
+                        (8, n as usize)
+                    };
+                let row_bytes = sprite_width / 8;
+
+                // XO-CHIP: each selected plane gets its own sprite, stored one after the other
+                let sprite_start = self.index_register as usize;
+                let sprite_bytes = sprite_height * row_bytes * self.planes.count_ones() as usize;
+                if sprite_start + sprite_bytes > self.memory.len() {
+                    return Err(Chip8Error::MemoryOutOfBounds(self.index_register));
+                }
                 self.registers[0xF] = 0; // Set VF to 0
 
-                for row in 0..n {
-                    let y = y_start + row;
-                    if y >= DISPLAY_HEIGHT as u8 {
-                        break;
+                let mut plane_start = sprite_start;
+                for plane in [PLANE_1, PLANE_2] {
+                    if self.planes & plane == 0 {
+                        continue;
                     }
-                    let sprite = self.memory[self.index_register as usize + row as usize];
 
-                    for col in 0..8 {
-                        // Check if the bite for the column is set
-                        let x = x_start + col;
-                        if x >= DISPLAY_WIDTH as u8 {
-                            break;
+                    for row in 0..sprite_height {
+                        let mut y = y_start + row;
+                        if y >= self.display.height() {
+                            if !self.quirks.wrap_sprites {
+                                break; // clip at the bottom edge
+                            }
+                            y %= self.display.height(); // wrap around to the top
                         }
-                        let on = (sprite >> (7 - col)) & 1 == 1;
-                        if on {
-                            if self.display[y as usize][x as usize] {
-                                self.registers[0xF] = 1; // sprite was active
+                        let row_start = plane_start + row * row_bytes;
+                        let sprite = self.memory[row_start..row_start + row_bytes]
+                            .iter()
+                            .fold(0u16, |bits, &byte| bits << 8 | byte as u16);
+
+                        for col in 0..sprite_width {
+                            // Check if the bite for the column is set
+                            let mut x = x_start + col;
+                            if x >= self.display.width() {
+                                if !self.quirks.wrap_sprites {
+                                    break; // clip at the right edge
+                                }
+                                x %= self.display.width(); // wrap around to the left
                             }
+                            let on = (sprite >> (sprite_width - 1 - col)) & 1 == 1;
+                            if on {
+                                let value = self.display.planes(x, y);
+                                let was_on = value & plane != 0;
+                                if was_on {
+                                    self.registers[0xF] = 1; // sprite was active
+                                    collisions += 1;
+                                }
 
-                            // Toggle the pixel
-                            // exclusive OR will only produce true if the two values are different
-                            // i.e. true ^ true = false and true ^ false = true
-                            self.display[y as usize][x as usize] ^= true;
+                                match self.draw_mode {
+                                    DrawMode::Xor => {
+                                        // Toggle the pixel on this plane
+                                        // exclusive OR will only produce true if the two values are different
+                                        // i.e. true ^ true = false and true ^ false = true
+                                        self.display.set_planes(x, y, value ^ plane);
+                                        if was_on {
+                                            pixels_cleared += 1;
+                                        } else {
+                                            pixels_set += 1;
+                                        }
+                                    }
+                                    DrawMode::Overwrite => {
+                                        // Set the pixel, leaving already lit pixels on
+                                        self.display.set_planes(x, y, value | plane);
+                                        if !was_on {
+                                            pixels_set += 1;
+                                        }
+                                    }
+                                }
+                            }
                         }
                     }
+                    plane_start += sprite_height * row_bytes;
+                }
+
+                self.watch(address, sprite_start, sprite_bytes, MemoryAccess::Read);
+                self.display_dirty = true;
+                self.last_collision_count = collisions;
+
+                // COSMAC VIP: the interpreter waited for the vertical blank after drawing
+                self.waiting_for_vblank = self.quirks.display_wait;
+
+                if let Some(hook) = self.draw_trace_hook.as_mut() {
+                    hook(&DrawTrace {
+                        sprite: self.memory[sprite_start..sprite_start + sprite_bytes].to_vec(),
+                        x: x_start,
+                        y: y_start,
+                        pixels_set,
+                        pixels_cleared,
+                        collision: self.registers[0xF] == 1,
+                    });
                 }
             }
             0xE => {
                 match (y, n) {
                     (0x9, 0xE) => {
                         // 0xEX9E: Skip if key == vx pressed
-                        println!(
-                            "Handling opcode: {:#x?} - skipping if key pressed == v{}",
-                            opcode, x
-                        );
-                        let key = self.registers[x as usize] as usize; // Key value from VX
-                        if key < 16 && self.keypad[key] {
-                            // use the less than 16 guard to prevent overflow crashing
-                            self.program_counter += 2;
+                        trace!(self, address, opcode, "skipping if key pressed == v{}", x);
+                        if self.key_state(vx) {
+                            // keys above F are never pressed
+                            self.skip_instruction();
                         }
                     }
                     (0xA, 0x1) => {
                         // 0xEXA1: Skip if key == vx not pressed
-                        println!(
-                            "Handling opcode: {:#x?} - skipping if key pressed != v{}",
-                            opcode, x
-                        );
-                        let key = self.registers[x as usize] as usize;
-                        if key < 16 && !self.keypad[key] {
-                            self.program_counter += 2;
+                        trace!(self, address, opcode, "skipping if key pressed != v{}", x);
+                        if (vx as usize) < NUM_KEYS && !self.key_state(vx) {
+                            self.skip_instruction();
                         }
                     }
-                    _ => panic!("Unimplemented opcode: {:#x?}", opcode),
+                    _ => return Err(Chip8Error::UnknownOpcode(opcode)),
                 }
             }
             0xF => {
@@ -439,33 +1377,34 @@ impl Chip8 {
                 match (y, n) {
                     (0x0, 0x7) => {
                         // 0xFX07: sets VX to the current value of the delay timer
-                        println!(
-                            "Handling opcode: {:#x?} - setting v{} to {}",
-                            opcode, x, self.delay_timer
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "setting v{} to {}",
+                            x,
+                            self.delay_timer
                         );
                         self.registers[x as usize] = self.delay_timer;
                     }
                     (0x1, 0x5) => {
                         // 0xFX15: set the delay timer to the value in VX
-                        println!(
-                            "Handling opcode: {:#x?} - setting delayer timer to v{}",
-                            opcode, x
-                        );
+                        trace!(self, address, opcode, "setting delay timer to v{}", x);
                         self.delay_timer = self.registers[x as usize];
                     }
                     (0x1, 0x8) => {
                         // 0xFX18: set the sound timer to the value of VX
-                        println!(
-                            "Handling opcode: {:#x?} - setting sound timer to v{}",
-                            opcode, x
-                        );
+                        trace!(self, address, opcode, "setting sound timer to v{}", x);
                         self.sound_timer = self.registers[x as usize];
                     }
                     (0x1, 0xE) => {
                         // 0xFX1E: Add to index
-                        println!(
-                            "Handling opcode: {:#x?} - adding value of v{} to index register",
-                            opcode, x
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "adding value of v{} to index register",
+                            x
                         );
                         let (val, overflow) = self.index_register.overflowing_add(vx as u16);
                         self.index_register = val;
@@ -473,29 +1412,71 @@ impl Chip8 {
                     }
                     (0x0, 0xA) => {
                         // 0xFX0A: Get Key
-                        println!("Handling opcode: {:#x?} - Getting Key", opcode);
-
-                        let mut wait = true; // indicate if wait is needed.
-                                             // check if key is pressed
-                        for (key, pressed) in self.keypad.iter().enumerate() {
-                            if *pressed {
-                                self.registers[x as usize] = key as u8;
-                                wait = false; // if key is pressed, exit wait state
-                                self.keypad[key] = false;
-                                break;
-                            }
-                        }
+                        // Like the COSMAC VIP, wait for a key to be pressed and then released,
+                        // so a key held down across frames only counts once
+                        trace!(self, address, opcode, "waiting for a key press");
 
-                        // wait until a key is pressed
-                        if wait {
-                            self.program_counter -= 2;
+                        self.waiting_for_key = true;
+                        match self.pressed_key {
+                            Some(key) if !self.key_state(key) => {
+                                // the key was released: store it and continue
+                                self.registers[x as usize] = key;
+                                self.pressed_key = None;
+                                self.waiting_for_key = false;
+                            }
+                            Some(_) => {
+                                // still held, run this instruction again, fetch may have wrapped to 0
+                                self.program_counter = self.program_counter.wrapping_sub(2);
+                            }
+                            None => {
+                                // remember the earliest pressed key still held and wait for its release
+                                while let Some(key) = self.key_presses.pop_front() {
+                                    if self.key_state(key) {
+                                        self.pressed_key = Some(key);
+                                        break;
+                                    }
+                                }
+                                self.program_counter = self.program_counter.wrapping_sub(2);
+                            }
                         }
                     }
+                    (0x0, 0x0) if x == 0 && self.quirks.xo_chip => {
+                        // 0xF000 NNNN: load I with the 16-bit address in the next word (XO-CHIP)
+                        let operand = self.program_counter as usize;
+                        let addr = self.fetch()?;
+                        self.watch(address, operand, 2, MemoryAccess::Read);
+                        trace!(self, address, opcode, "setting index register to {}", addr);
+                        self.index_register = addr;
+                    }
+                    (0x0, 0x2) if x == 0 && self.quirks.xo_chip => {
+                        // 0xF002: load the 16-byte audio pattern at I (XO-CHIP)
+                        trace!(self, address, opcode, "loading audio pattern");
+                        let start = self.index_register as usize;
+                        let pattern = self
+                            .memory
+                            .get(start..start + AUDIO_PATTERN_SIZE)
+                            .ok_or(Chip8Error::MemoryOutOfBounds(self.index_register))?;
+                        self.audio_pattern.copy_from_slice(pattern);
+                        self.watch(address, start, AUDIO_PATTERN_SIZE, MemoryAccess::Read);
+                    }
+                    (0x3, 0xA) if self.quirks.xo_chip => {
+                        // 0xFX3A: set the audio pattern pitch to VX (XO-CHIP)
+                        trace!(self, address, opcode, "setting pitch to v{}", x);
+                        self.pitch = vx;
+                    }
+                    (0x0, 0x1) if self.quirks.xo_chip => {
+                        // 0xFN01: select the bitplanes drawn on (XO-CHIP)
+                        trace!(self, address, opcode, "selecting planes {:#04b}", x);
+                        self.planes = x & ALL_PLANES;
+                    }
                     (0x2, 0x9) => {
                         // OxFX29: Font Character
-                        println!(
-                            "Handling opcode: {:#x?} - setting index register to font at v{}",
-                            opcode, x
+                        trace!(
+                            self,
+                            address,
+                            opcode,
+                            "setting index register to font at v{}",
+                            x
                         );
                         let character = vx & 0xF; // Get the last nibble of VX and set it as character
                         self.index_register = FONT_START as u16 + (0x5 * character) as u16
@@ -504,78 +1485,166 @@ impl Chip8 {
                     (0x3, 0x3) => {
                         // 0xFX33: Binary-coded decimal conversion
                         // vx = a number from 0 to 255
-                        println!(
-                            "Handling opcode: {:#x?} - converting v{} to decimal",
-                            opcode, x
-                        );
+                        trace!(self, address, opcode, "converting v{} to decimal", x);
                         let hundreds = vx / 100; // will give the value at 100 and truncate remainders
                         let tens = (vx % 100) / 10; // get the remainder by eliminating the 100 digit and divide by 10
                         let units = vx % 10; // get the remainder by modulo 10
 
-                        self.memory[self.index_register as usize] = hundreds;
-                        self.memory[self.index_register as usize + 1] = tens;
-                        self.memory[self.index_register as usize + 2] = units;
+                        let start = self.index_register as usize;
+                        if start + 3 > self.memory.len() {
+                            return Err(Chip8Error::MemoryOutOfBounds(self.index_register));
+                        }
+                        self.memory[start..start + 3].copy_from_slice(&[hundreds, tens, units]);
+                        self.watch(address, start, 3, MemoryAccess::Write);
                     }
                     (0x5, 0x5) => {
                         // 0xFX55: store register value from 0..X into memory
-                        println!(
-                            "Handling opcode: {:#x?} - copying {} values from registers",
-                            opcode, x
-                        );
-                        // TODO: configure for backwards compatability
-                        for i in 0..=x {
-                            self.memory[(self.index_register + i as u16) as usize] =
-                                self.registers[i as usize];
-                            println!(
-                                "Ram location is at: {} with value: {}",
-                                self.index_register + i as u16,
-                                self.memory[(self.index_register + i as u16) as usize]
-                            );
+                        trace!(self, address, opcode, "copying {} values from registers", x);
+                        let start = self.index_register as usize;
+                        let count = x as usize + 1;
+                        if start + count > self.memory.len() {
+                            return Err(Chip8Error::MemoryOutOfBounds(self.index_register));
+                        }
+                        self.memory[start..start + count].copy_from_slice(&self.registers[..count]);
+                        self.watch(address, start, count, MemoryAccess::Write);
+                        if self.quirks.memory_increments_index {
+                            // COSMAC VIP: I was incremented as each register was stored
+                            self.index_register = self.index_register.wrapping_add(x as u16 + 1);
                         }
                     }
                     (0x6, 0x5) => {
-                        // 0xF65:
-                        // TODO: configure for backwards compatability
-                        println!(
-                            "Handling opcode: {:#x?} - copying {} values to registers",
-                            opcode, x
-                        );
-                        for i in 0..=x {
-                            self.registers[i as usize] =
-                                self.memory[(self.index_register + i as u16) as usize];
-                            println!(
-                                "Register location is at: {} with value: {}",
-                                i, self.registers[i as usize]
-                            );
+                        // 0xFX65: load register value from memory into 0..X
+                        trace!(self, address, opcode, "copying {} values to registers", x);
+                        let start = self.index_register as usize;
+                        let count = x as usize + 1;
+                        if start + count > self.memory.len() {
+                            return Err(Chip8Error::MemoryOutOfBounds(self.index_register));
+                        }
+                        self.registers[..count].copy_from_slice(&self.memory[start..start + count]);
+                        self.watch(address, start, count, MemoryAccess::Read);
+                        if self.quirks.memory_increments_index {
+                            // COSMAC VIP: I was incremented as each register was loaded
+                            self.index_register = self.index_register.wrapping_add(x as u16 + 1);
                         }
                     }
-                    _ => panic!("Unimplemented opcode: {:#x?}", opcode),
+                    _ => return Err(Chip8Error::UnknownOpcode(opcode)),
                 }
             }
-            _ => panic!("Unimplemented opcode: {:#x?}", opcode),
+            _ => return Err(Chip8Error::UnknownOpcode(opcode)),
+        }
+
+        self.check_debug_targets();
+        Ok(Some(decoded))
+    }
+
+    /// Function to request a break once a step count, run-to address or breakpoint is reached
+    fn check_debug_targets(&mut self) {
+        if let Some(steps) = self.steps_until_break {
+            if steps <= 1 {
+                self.steps_until_break = None;
+                self.break_requested = true;
+            } else {
+                self.steps_until_break = Some(steps - 1);
+            }
+        }
+
+        if self.run_to_address == Some(self.program_counter) {
+            self.run_to_address = None;
+            self.break_requested = true;
+        }
+
+        if self.is_at_breakpoint() {
+            // break before the next instruction, which then runs on the following step
+            self.break_requested = true;
+            self.stopped_at = Some(self.program_counter);
+        }
+    }
+
+    /// Function to record the watchpoints in the `len` bytes at `start` accessed by the
+    /// instruction at `instruction`, and request a break if there are any
+    fn watch(&mut self, instruction: u16, start: usize, len: usize, access: MemoryAccess) {
+        if self.watchpoints.is_empty() {
+            return;
+        }
+        let end = start + len;
+        let hits: Vec<u16> = self
+            .watchpoints
+            .range(start as u16..)
+            .take_while(|&&addr| (addr as usize) < end)
+            .copied()
+            .collect();
+        for addr in hits {
+            self.watch_hits.push(WatchHit {
+                addr,
+                access,
+                instruction,
+            });
+            self.break_requested = true;
+        }
+    }
+
+    /// Function to clear VF after a logical operation, as the COSMAC VIP did
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
         }
     }
 
+    /// Function to skip the next instruction.
+    /// With XO-CHIP the 4-byte F000 NNNN instruction is skipped as a whole.
+    fn skip_instruction(&mut self) {
+        let pc = self.program_counter as usize;
+        let long = self.quirks.xo_chip
+            && pc + 1 < self.memory.len()
+            && self.memory[pc] == 0xF0
+            && self.memory[pc + 1] == 0x00;
+        self.program_counter = self.program_counter.wrapping_add(if long { 4 } else { 2 });
+    }
+
     /// Function to call_subroutine subroutine at address location
-    fn call_subroutine(&mut self, addr: u16) {
+    fn call_subroutine(&mut self, addr: u16) -> Result<(), Chip8Error> {
         // Guard to prevent stack overflow
         if self.stack_pointer >= self.stack.len() {
-            panic!("Stack overflow!")
+            return Err(Chip8Error::StackOverflow);
         }
         self.stack[self.stack_pointer] = self.program_counter; // pushing into the current stack location
         self.stack_pointer += 1;
         self.program_counter = addr; // set program counter to the nnn address
+        Ok(())
     }
 
     /// Function to return the subroutine and setting the address
-    fn return_subroutine(&mut self) {
+    fn return_subroutine(&mut self) -> Result<(), Chip8Error> {
         // Guard to prevent stack underflow
         if self.stack_pointer == 0 {
-            panic!("Stack underflow!")
+            return Err(Chip8Error::StackUnderflow);
         }
         self.stack_pointer -= 1;
         let addr = self.stack[self.stack_pointer];
         self.program_counter = addr;
+        Ok(())
+    }
+
+    /// Function shifting VX (or VY with the `shift_uses_vy` quirk) right, VF gets the bit shifted out
+    fn shift_right(&mut self, x: u8, y: u8) {
+        let source = if self.quirks.shift_uses_vy { y } else { x };
+        let value = self.registers[source as usize]; // value before the shift operation
+        self.set_result_and_flag(x, value >> 1, value & 0b0000_0001);
+    }
+
+    /// Function shifting VX (or VY with the `shift_uses_vy` quirk) left, VF gets the bit shifted out
+    fn shift_left(&mut self, x: u8, y: u8) {
+        let source = if self.quirks.shift_uses_vy { y } else { x };
+        let value = self.registers[source as usize]; // value before the shift operation
+        self.set_result_and_flag(x, value << 1, value >> 7);
+    }
+
+    /// Function storing the result of an 8XYN operation in VX, then its flag in VF.
+    /// The operands are read before anything is written and VF is always written last,
+    /// so when X is VF the register ends up holding the flag, as on the original interpreter.
+    fn set_result_and_flag(&mut self, x: u8, result: u8, flag: u8) {
+        self.registers[x as usize] = result;
+        self.registers[0xF] = flag;
     }
 
     /// Function adding x and y values while setting the reminder bit
@@ -584,10 +1653,7 @@ impl Chip8 {
         let vy = self.registers[y as usize];
 
         let (val, overflow) = vx.overflowing_add(vy);
-        self.registers[x as usize] = val;
-
-        // set the overflow register
-        self.registers[0xF] = if overflow { 1 } else { 0 };
+        self.set_result_and_flag(x, val, if overflow { 1 } else { 0 });
     }
 
     /// Function subtracting x and y values in the register while setting the reminder bit
@@ -595,11 +1661,10 @@ impl Chip8 {
         let vx = self.registers[x as usize];
         let vy = self.registers[y as usize];
 
+        // compute the borrow flag from the operands before anything is written
+        let not_borrow = if vx >= vy { 1 } else { 0 };
         let val = vx.wrapping_sub(vy);
-        self.registers[x as usize] = val;
-
-        // setting the overflow register
-        self.registers[0xF] = if vx > vy { 1 } else { 0 };
+        self.set_result_and_flag(x, val, not_borrow);
     }
 
     /// Function subtracting y and x values in the register while setting the reminder bit
@@ -607,10 +1672,164 @@ impl Chip8 {
         let vx = self.registers[x as usize];
         let vy = self.registers[y as usize];
 
+        // compute the borrow flag from the operands before anything is written
+        let not_borrow = if vy >= vx { 1 } else { 0 };
         let val = vy.wrapping_sub(vx);
-        self.registers[x as usize] = val;
+        self.set_result_and_flag(x, val, not_borrow);
+    }
+}
+
+/// The parts of the CPU that make up a save state
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Snapshot {
+    memory: Vec<u8>,
+    display: Vec<u8>, // planes of the active pixels, row by row
+    hi_res: bool,
+    planes: u8,
+    audio_pattern: Vec<u8>,
+    pitch: u8,
+    program_counter: u16,
+    index_register: u16,
+    stack: Vec<u16>,
+    stack_pointer: usize,
+    delay_timer: u8,
+    sound_timer: u8,
+    registers: Vec<u8>,
+    keypad: Vec<bool>,
+    key_presses: Vec<u8>,
+    pressed_key: Option<u8>,
+    waiting_for_key: bool,
+    waiting_for_vblank: bool,
+    rng: ChaCha12Rng, // position in the random numbers, so CXNN gives the same ones after a rewind
+}
+
+#[cfg(feature = "serde")]
+impl Chip8 {
+    /// A function to serialize the CPU state into a compact binary save state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut display = Vec::with_capacity(self.display.width() * self.display.height());
+        for y in 0..self.display.height() {
+            for x in 0..self.display.width() {
+                display.push(self.display.planes(x, y));
+            }
+        }
+
+        let snapshot = Snapshot {
+            memory: self.memory.clone(),
+            display,
+            hi_res: self.hi_res,
+            planes: self.planes,
+            audio_pattern: self.audio_pattern.to_vec(),
+            pitch: self.pitch,
+            program_counter: self.program_counter,
+            index_register: self.index_register,
+            stack: self.stack.to_vec(),
+            stack_pointer: self.stack_pointer,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            registers: self.registers.to_vec(),
+            keypad: self.keypad.to_vec(),
+            key_presses: self.key_presses.iter().copied().collect(),
+            pressed_key: self.pressed_key,
+            waiting_for_key: self.waiting_for_key,
+            waiting_for_vblank: self.waiting_for_vblank,
+            rng: self.rng.clone(),
+        };
+        bincode::serialize(&snapshot).expect("save state serialization cannot fail")
+    }
+
+    /// A function to keep the last `capacity` CPU states, one per `cycle`, so that
+    /// `rewind` can step backwards. A capacity of 0 disables the history.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        while self.history.len() > capacity {
+            self.history.pop_front();
+        }
+    }
+
+    /// A function to undo the last cycle by restoring the most recent state of the history.
+    /// Returns `false` when there is nothing left to rewind.
+    pub fn rewind(&mut self) -> bool {
+        let Some(state) = self.history.pop_back() else {
+            return false;
+        };
+        self.load_state(&state)
+            .expect("states recorded by the history are always compatible");
+        true
+    }
+
+    /// Function to push the current state to the history, dropping the oldest one when full
+    fn record_history(&mut self) {
+        if self.history_capacity == 0 {
+            return;
+        }
+        if self.history.len() == self.history_capacity {
+            self.history.pop_front();
+        }
+        let state = self.save_state();
+        self.history.push_back(state);
+    }
+
+    /// A function to restore a save state produced by `save_state`.
+    /// The CPU is left untouched if the save state is truncated or incompatible.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), StateError> {
+        let snapshot: Snapshot =
+            bincode::deserialize(bytes).map_err(|error| StateError::Corrupt(error.to_string()))?;
+
+        // Check every buffer matches this CPU before changing anything
+        let mut display = Framebuffer::new();
+        display.set_hi_res(snapshot.hi_res);
+        if snapshot.memory.len() != self.memory.len() {
+            return Err(StateError::Incompatible("memory size"));
+        }
+        let stack: [u16; STACK_SIZE] = snapshot
+            .stack
+            .try_into()
+            .map_err(|_| StateError::Incompatible("stack size"))?;
+        let registers: [u8; NUM_REGISTERS] = snapshot
+            .registers
+            .try_into()
+            .map_err(|_| StateError::Incompatible("register count"))?;
+        let audio_pattern: [u8; AUDIO_PATTERN_SIZE] = snapshot
+            .audio_pattern
+            .try_into()
+            .map_err(|_| StateError::Incompatible("audio pattern size"))?;
+        let keypad: [bool; NUM_KEYS] = snapshot
+            .keypad
+            .try_into()
+            .map_err(|_| StateError::Incompatible("keypad size"))?;
+        if snapshot.display.len() != display.width() * display.height() {
+            return Err(StateError::Incompatible("display size"));
+        }
+        if snapshot.stack_pointer > STACK_SIZE {
+            return Err(StateError::Incompatible("stack pointer"));
+        }
+
+        for (i, planes) in snapshot.display.into_iter().enumerate() {
+            display.set_planes(i % display.width(), i / display.width(), planes);
+        }
 
-        // setting the overflow register
-        self.registers[0xF] = if vy > vx { 1 } else { 0 };
+        self.memory = snapshot.memory;
+        self.display = display;
+        self.display_dirty = true;
+        self.hi_res = snapshot.hi_res;
+        self.planes = snapshot.planes & ALL_PLANES;
+        self.audio_pattern = audio_pattern;
+        self.pitch = snapshot.pitch;
+        self.program_counter = snapshot.program_counter;
+        self.index_register = snapshot.index_register;
+        self.stack = stack;
+        self.stack_pointer = snapshot.stack_pointer;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.registers = registers;
+        self.keypad = keypad;
+        self.key_presses = snapshot.key_presses.into();
+        self.pressed_key = snapshot.pressed_key;
+        self.waiting_for_key = snapshot.waiting_for_key;
+        self.waiting_for_vblank = snapshot.waiting_for_vblank;
+        self.rng = snapshot.rng;
+        Ok(())
     }
 }
@@ -0,0 +1,94 @@
+/// Kind of memory access made by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccess {
+    Read,
+    Write,
+}
+
+/// An access to a watched address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub addr: u16,            // the watched address
+    pub access: MemoryAccess, // whether it was read or written
+    pub instruction: u16,     // address of the instruction that accessed it
+}
+
+/// Commands accepted at the debugger prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    Continue,      // resume normal execution
+    Step(usize),   // execute N instructions then break again
+    RunTo(u16),    // resume until the program counter reaches an address once
+    Break(u16),    // add a breakpoint at an address
+    Delete(u16),   // remove the breakpoint at an address
+    Watch(u16),    // add a watchpoint at an address
+    Unwatch(u16),  // remove the watchpoint at an address
+    Peek(u16),     // print the byte at an address
+    Poke(u16, u8), // write a byte at an address
+}
+
+impl DebugCommand {
+    /// Parse a line typed at the debugger prompt.
+    /// An empty line continues, `step` alone steps a single instruction.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            None | Some("c") | Some("continue") => DebugCommand::Continue,
+            Some("s") | Some("step") => match words.next() {
+                None => DebugCommand::Step(1),
+                Some(count) => DebugCommand::Step(
+                    count
+                        .parse()
+                        .map_err(|_| format!("invalid step count: {}", count))?,
+                ),
+            },
+            Some("run-to") => {
+                let addr = words.next().ok_or("run-to needs an address")?;
+                DebugCommand::RunTo(parse_address(addr)?)
+            }
+            Some("b") | Some("break") => {
+                let addr = words.next().ok_or("break needs an address")?;
+                DebugCommand::Break(parse_address(addr)?)
+            }
+            Some("d") | Some("delete") => {
+                let addr = words.next().ok_or("delete needs an address")?;
+                DebugCommand::Delete(parse_address(addr)?)
+            }
+            Some("w") | Some("watch") => {
+                let addr = words.next().ok_or("watch needs an address")?;
+                DebugCommand::Watch(parse_address(addr)?)
+            }
+            Some("unwatch") => {
+                let addr = words.next().ok_or("unwatch needs an address")?;
+                DebugCommand::Unwatch(parse_address(addr)?)
+            }
+            Some("peek") => {
+                let addr = words.next().ok_or("peek needs an address")?;
+                DebugCommand::Peek(parse_address(addr)?)
+            }
+            Some("poke") => {
+                let addr = words.next().ok_or("poke needs an address and a byte")?;
+                let byte = words.next().ok_or("poke needs a byte")?;
+                DebugCommand::Poke(parse_address(addr)?, parse_byte(byte)?)
+            }
+            Some(other) => return Err(format!("unknown command: {}", other)),
+        };
+
+        match words.next() {
+            Some(extra) => Err(format!("unexpected argument: {}", extra)),
+            None => Ok(command),
+        }
+    }
+}
+
+/// Parse a hexadecimal address, with or without the `0x` prefix
+fn parse_address(addr: &str) -> Result<u16, String> {
+    u16::from_str_radix(addr.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid address: {}", addr))
+}
+
+/// Parse a hexadecimal byte, with or without the `0x` prefix
+fn parse_byte(byte: &str) -> Result<u8, String> {
+    u8::from_str_radix(byte.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid byte: {}", byte))
+}
@@ -0,0 +1,104 @@
+use crate::OpCode;
+
+/// Disassemble a ROM loaded at `start` into `(address, mnemonic)` pairs, e.g. `(0x200, "JP 0x2A8")`.
+/// `start` is the program start of the CPU, `PROGRAM_START` unless it was changed, e.g. to
+/// 0x600 for ETI-660 programs.
+/// Words that are not instructions are shown as `DW 0xNNNN`, and a trailing
+/// odd byte as `DB 0xNN`.
+pub fn disassemble(rom: &[u8], start: u16) -> Vec<(u16, String)> {
+    let mut listing = Vec::with_capacity(rom.len() / 2 + 1);
+
+    for (i, chunk) in rom.chunks(2).enumerate() {
+        let addr = start.wrapping_add((i * 2) as u16);
+        let line = match chunk {
+            [high, low] => {
+                let opcode = (*high as u16) << 8 | *low as u16;
+                mnemonic(opcode).unwrap_or_else(|| format!("DW 0x{:04X}", opcode))
+            }
+            [byte] => format!("DB 0x{:02X}", byte),
+            _ => unreachable!("chunks(2) yields one or two bytes"),
+        };
+        listing.push((addr, line));
+    }
+
+    listing
+}
+
+/// Turn a single opcode into its mnemonic, or `None` if it is not an instruction
+pub fn mnemonic(opcode: u16) -> Option<String> {
+    let OpCode {
+        c,
+        x,
+        y,
+        n,
+        nn,
+        nnn,
+    } = OpCode::decode(&opcode);
+
+    // matching the operation category first, as the CPU does
+    let text = match c {
+        0x0 => match (x, y, n) {
+            (0, 0xE, 0) => "CLS".to_string(),
+            (0, 0xE, 0xE) => "RET".to_string(),
+            (0, 0xC, n) => format!("SCD {}", n),
+            (0, 0xF, 0xB) => "SCR".to_string(),
+            (0, 0xF, 0xC) => "SCL".to_string(),
+            (0, 0xF, 0xE) => "LOW".to_string(),
+            (0, 0xF, 0xF) => "HIGH".to_string(),
+            _ => format!("SYS 0x{:03X}", nnn),
+        },
+        0x1 => format!("JP 0x{:03X}", nnn),
+        0x2 => format!("CALL 0x{:03X}", nnn),
+        0x3 => format!("SE V{:X}, 0x{:02X}", x, nn),
+        0x4 => format!("SNE V{:X}, 0x{:02X}", x, nn),
+        0x5 if n == 0 => format!("SE V{:X}, V{:X}", x, y),
+        0x5 if n == 2 => format!("SAVE V{:X}-V{:X}", x, y),
+        0x5 if n == 3 => format!("LOAD V{:X}-V{:X}", x, y),
+        0x6 => format!("LD V{:X}, 0x{:02X}", x, nn),
+        0x7 => format!("ADD V{:X}, 0x{:02X}", x, nn),
+        0x8 => {
+            let operation = match n {
+                0x0 => "LD",
+                0x1 => "OR",
+                0x2 => "AND",
+                0x3 => "XOR",
+                0x4 => "ADD",
+                0x5 => "SUB",
+                0x6 => "SHR",
+                0x7 => "SUBN",
+                0xE => "SHL",
+                _ => return None,
+            };
+            format!("{} V{:X}, V{:X}", operation, x, y)
+        }
+        0x9 if n == 0 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA => format!("LD I, 0x{:03X}", nnn),
+        0xB => format!("JP V0, 0x{:03X}", nnn),
+        0xC => format!("RND V{:X}, 0x{:02X}", x, nn),
+        0xD => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE => match (y, n) {
+            (0x9, 0xE) => format!("SKP V{:X}", x),
+            (0xA, 0x1) => format!("SKNP V{:X}", x),
+            _ => return None,
+        },
+        0xF => match (y, n) {
+            (0x0, 0x0) if x == 0 => "LD I, LONG".to_string(),
+            (0x0, 0x1) => format!("PLANE {}", x),
+            (0x0, 0x2) if x == 0 => "AUDIO".to_string(),
+            (0x0, 0x7) => format!("LD V{:X}, DT", x),
+            (0x0, 0xA) => format!("LD V{:X}, K", x),
+            (0x1, 0x5) => format!("LD DT, V{:X}", x),
+            (0x1, 0x8) => format!("LD ST, V{:X}", x),
+            (0x1, 0xE) => format!("ADD I, V{:X}", x),
+            (0x2, 0x9) => format!("LD F, V{:X}", x),
+            (0x3, 0x3) => format!("LD B, V{:X}", x),
+            (0x3, 0xA) => format!("PITCH V{:X}", x),
+            (0x5, 0x5) => format!("LD [I], V{:X}", x),
+            (0x6, 0x5) => format!("LD V{:X}, [I]", x),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(text)
+}
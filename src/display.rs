@@ -2,62 +2,317 @@ extern crate sdl2;
 
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
 use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::render::Texture;
+use sdl2::video::{FullscreenType, Window};
 use sdl2::Sdl;
 
-const DISPLAY_WIDTH: u32 = 64; // Default display width
-const DISPLAY_HEIGHT: u32 = 32; // Default pixel height
+use crate::{Framebuffer, PreviousFrame, Renderer, DISPLAY_HEIGHT, DISPLAY_WIDTH, FONTS, PLANE_1};
+
+/// Black background, white first plane, and two grays for the second XO-CHIP plane and the overlap
+pub const DEFAULT_PALETTE: [Color; 4] = [
+    Color::RGB(0, 0, 0),
+    Color::RGB(255, 255, 255),
+    Color::RGB(170, 170, 170),
+    Color::RGB(85, 85, 85),
+];
+
 pub struct Display {
     canvas: Canvas<Window>,
-    scale: u32,
-    background_color: Color,
-    foreground_color: Color,
+    scale: u32,          // size of a standard resolution pixel, follows the window size
+    offset: (i32, i32),  // top-left corner of the screen, centered in the window
+    palette: [Color; 4], // colors indexed by the planes a pixel is lit on, 0 being the background
+    diff_draw: bool,     // only redraw the pixels that changed since the last frame
+    previous: PreviousFrame, // the last frame drawn, forgotten when a full redraw is needed
+    target: Option<Texture>, // the pixels kept between frames with diff drawing, window sized
+    stats: Option<(u32, u64)>, // frames and instructions per second shown in the top-left corner
 }
 
 impl Display {
-    pub fn new(sdl_context: &Sdl, scale: u32) -> Self {
-        let video_subsystem = sdl_context.video().unwrap();
+    pub fn new(sdl_context: &Sdl, scale: u32) -> Result<Self, String> {
+        let video_subsystem = sdl_context.video()?;
         let window = video_subsystem
-            .window("Chip-8", DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale)
+            .window(
+                "Chip-8",
+                DISPLAY_WIDTH as u32 * scale,
+                DISPLAY_HEIGHT as u32 * scale,
+            )
             .position_centered()
+            .resizable()
             .opengl()
             .build()
-            .unwrap();
-        let canvas = window.into_canvas().build().unwrap();
+            .map_err(|error| error.to_string())?;
+        let canvas = window
+            .into_canvas()
+            .build()
+            .map_err(|error| error.to_string())?;
 
-        Self {
+        Ok(Self {
             canvas,
             scale,
-            background_color: Color::RGB(0, 0, 0),
-            foreground_color: Color::RGB(255, 255, 255),
+            offset: (0, 0),
+            palette: DEFAULT_PALETTE,
+            diff_draw: false,
+            previous: PreviousFrame::new(),
+            target: None,
+            stats: None,
+        })
+    }
+
+    /// Change the colors of the background and of the lit pixels
+    pub fn set_colors(&mut self, background: Color, foreground: Color) {
+        self.palette[0] = background;
+        self.palette[PLANE_1 as usize] = foreground;
+        self.previous.forget(); // every pixel has to be repainted
+    }
+
+    /// Change the four colors of the XO-CHIP plane combinations: the background, pixels lit on
+    /// the first plane, on the second plane and on both. CHIP-8 and SUPER-CHIP only use the first two.
+    pub fn set_palette(&mut self, palette: [Color; 4]) {
+        self.palette = palette;
+        self.previous.forget(); // every pixel has to be repainted
+    }
+
+    /// Only redraw the pixels that changed since the last frame instead of the whole screen.
+    /// SDL does not keep the window contents once presented, so the pixels are drawn into
+    /// a texture that is copied to the window on every frame. Renderers that cannot draw
+    /// into textures fall back to redrawing the whole screen.
+    pub fn set_diff_draw(&mut self, diff_draw: bool) {
+        self.diff_draw = diff_draw;
+        self.previous.forget();
+        if !diff_draw {
+            self.destroy_target();
+        }
+    }
+
+    /// Function to create the texture the pixels are drawn into with diff drawing,
+    /// again whenever the window size changed
+    fn update_target(&mut self) {
+        let Ok((width, height)) = self.canvas.output_size() else {
+            return;
+        };
+        if let Some(target) = &self.target {
+            let query = target.query();
+            if (query.width, query.height) == (width, height) {
+                return;
+            }
+        }
+
+        self.destroy_target();
+        self.previous.forget(); // a new texture has to be painted whole
+        self.target = self
+            .canvas
+            .texture_creator()
+            .create_texture_target(None, width, height)
+            .ok();
+        if let Some(target) = &mut self.target {
+            target.set_blend_mode(BlendMode::None); // copied over whatever the window holds
         }
     }
 
-    pub fn draw(
-        self: &mut Display,
-        buffer: &[[bool; DISPLAY_WIDTH as usize]; DISPLAY_HEIGHT as usize],
+    /// Function to free the texture used by diff drawing
+    fn destroy_target(&mut self) {
+        if let Some(target) = self.target.take() {
+            // SAFETY: the texture belongs to the renderer of `canvas`, which is still alive,
+            // and it was taken out of `self` so it cannot be used again
+            unsafe { target.destroy() };
+        }
+    }
+
+    /// Function to get the scaled rectangle covering the pixel at column `x` and row `y`
+    fn pixel_rect(&self, x: usize, y: usize, pixel_size: u32) -> Rect {
+        Rect::new(
+            self.offset.0 + (x as u32 * pixel_size) as i32,
+            self.offset.1 + (y as u32 * pixel_size) as i32,
+            pixel_size,
+            pixel_size,
+        )
+    }
+
+    /// Function to get the scaled rectangles of the pixels to paint, batched by their planes
+    /// from unlit (0) to lit on both planes (3): the pixels that differ from `previous`,
+    /// or all the lit pixels when there is no previous frame to draw over
+    fn pixel_batches(
+        &self,
+        buffer: &Framebuffer,
+        previous: Option<&Framebuffer>,
+    ) -> [Vec<Rect>; 4] {
+        // The window is sized for the standard resolution, so pixels shrink in high resolution
+        let pixel_size = DISPLAY_WIDTH as u32 * self.scale / buffer.width() as u32;
+        let mut batches: [Vec<Rect>; 4] = Default::default();
+        for y in 0..buffer.height() {
+            for x in 0..buffer.width() {
+                let planes = buffer.planes(x, y);
+                let paint = match previous {
+                    Some(previous) => planes != previous.planes(x, y),
+                    None => planes != 0,
+                };
+                if paint {
+                    batches[planes as usize].push(self.pixel_rect(x, y, pixel_size));
+                }
+            }
+        }
+        batches
+    }
+
+    /// Function to paint batches of pixels in the color of their planes, each batch in
+    /// a single call, after filling the canvas with the background when `clear` is set
+    fn fill_pixels(
+        canvas: &mut Canvas<Window>,
+        palette: &[Color; 4],
+        batches: &[Vec<Rect>; 4],
+        clear: bool,
     ) {
-        self.canvas.set_draw_color(self.background_color);
-        self.canvas.clear();
+        if clear {
+            canvas.set_draw_color(palette[0]);
+            canvas.clear();
+        }
+        for (planes, rects) in batches.iter().enumerate() {
+            if !rects.is_empty() {
+                canvas.set_draw_color(palette[planes]);
+                canvas.fill_rects(rects).unwrap();
+            }
+        }
+    }
 
-        self.canvas.set_draw_color(self.foreground_color);
-        // Draw each pixel
-        for y in 0..DISPLAY_HEIGHT {
-            for x in 0..DISPLAY_WIDTH {
-                if buffer[y as usize][x as usize] {
-                    // Draw a scaled rectangle for each pixel
-                    let rect = Rect::new(
-                        (x * self.scale) as i32,
-                        (y * self.scale) as i32,
-                        self.scale,
-                        self.scale,
-                    );
-                    self.canvas.fill_rect(rect).unwrap();
+    /// Draw the display buffer onto the window without showing it yet, see `present`.
+    /// Everything drawn since the last `present` is kept, so overlays can be added on top.
+    pub fn render(&mut self, buffer: &Framebuffer) {
+        self.render_pixels(buffer);
+        if let Some((fps, ips)) = self.stats {
+            self.draw_stats(fps, ips);
+        }
+    }
+
+    /// Function to draw the display buffer, only the pixels that changed with diff drawing
+    fn render_pixels(&mut self, buffer: &Framebuffer) {
+        if self.diff_draw {
+            self.update_target();
+            let previous = self.previous.replace(buffer);
+            let batches = self.pixel_batches(buffer, previous.as_ref());
+            if let Some(target) = &mut self.target {
+                let palette = self.palette;
+                let drawn = self.canvas.with_texture_canvas(target, |canvas| {
+                    Self::fill_pixels(canvas, &palette, &batches, previous.is_none());
+                });
+                if drawn.is_ok() {
+                    self.canvas.copy(target, None, None).unwrap();
+                    return;
+                }
+            }
+
+            eprintln!("Diff drawing needs render targets, redrawing the whole screen instead");
+            self.set_diff_draw(false);
+        }
+
+        let batches = self.pixel_batches(buffer, None);
+        Self::fill_pixels(&mut self.canvas, &self.palette, &batches, true);
+    }
+
+    /// Function to write the frame and instruction rates in the top-left corner, one per line,
+    /// with the CHIP-8 font over a dark backdrop
+    fn draw_stats(&mut self, fps: u32, ips: u64) {
+        let lines = [fps.to_string(), ips.to_string()];
+        let dot = (self.scale / 2).max(1); // size of a font pixel
+        let columns = lines.iter().map(String::len).max().unwrap_or(0) as u32 * 5 + 1;
+        let rows = lines.len() as u32 * 6 + 1;
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+        self.canvas
+            .fill_rect(Rect::new(
+                self.offset.0,
+                self.offset.1,
+                columns * dot,
+                rows * dot,
+            ))
+            .unwrap();
+        self.canvas.set_blend_mode(BlendMode::None);
+
+        let mut rects = Vec::new();
+        for (line, text) in lines.iter().enumerate() {
+            for (position, digit) in text.bytes().enumerate() {
+                // each glyph is 4x5 pixels, followed by a pixel of spacing
+                let glyph = &FONTS[(digit - b'0') as usize * 5..][..5];
+                for (y, row) in glyph.iter().enumerate() {
+                    for x in 0..4 {
+                        if row & (0x80 >> x) != 0 {
+                            rects.push(Rect::new(
+                                self.offset.0 + ((1 + position * 5 + x) as u32 * dot) as i32,
+                                self.offset.1 + ((1 + line * 6 + y) as u32 * dot) as i32,
+                                dot,
+                                dot,
+                            ));
+                        }
+                    }
                 }
             }
         }
+        self.canvas.set_draw_color(self.palette[PLANE_1 as usize]);
+        self.canvas.fill_rects(&rects).unwrap();
+    }
 
+    /// Fill the whole window with the background color
+    pub fn clear(&mut self) {
+        self.canvas.set_draw_color(self.palette[0]);
+        self.canvas.clear();
+    }
+
+    /// Darken what was drawn so far, e.g. to show that the game is paused
+    pub fn dim(&mut self) {
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        self.canvas.fill_rect(None).unwrap();
+        self.canvas.set_blend_mode(BlendMode::None);
+    }
+
+    /// Show on screen what was drawn since the last call
+    pub fn present(&mut self) {
         self.canvas.present();
     }
 }
+
+impl Renderer for Display {
+    fn draw(&mut self, buffer: &Framebuffer) {
+        self.render(buffer);
+        self.present();
+    }
+
+    fn draw_paused(&mut self, buffer: &Framebuffer) {
+        self.render(buffer);
+        self.dim();
+        self.present();
+        // the next frame is painted whole, so no dimmed pixel is left over after the pause
+        self.previous.forget();
+    }
+
+    fn set_stats(&mut self, fps: u32, ips: u64) {
+        self.stats = Some((fps, ips));
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        // The largest scale fitting the window while keeping the 2:1 aspect ratio,
+        // the rest of the window is left as background on both sides
+        self.scale = (width / DISPLAY_WIDTH as u32)
+            .min(height / DISPLAY_HEIGHT as u32)
+            .max(1);
+        self.offset = (
+            (width as i32 - (DISPLAY_WIDTH as u32 * self.scale) as i32) / 2,
+            (height as i32 - (DISPLAY_HEIGHT as u32 * self.scale) as i32) / 2,
+        );
+        self.previous.forget(); // every pixel moved
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        // the scale is recomputed by `resize` when SDL reports the new window size
+        if let Err(error) = window.set_fullscreen(fullscreen) {
+            eprintln!("Unable to toggle fullscreen: {}", error);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+/// Errors that can be raised by the Chip-8 CPU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    MemoryOutOfBounds(u16), // an access was made outside of the addressable memory
+    ProgramCounterOutOfBounds(u16), // an instruction was fetched past the end of memory
+    UnknownOpcode(u16),     // the instruction is not implemented
+    MachineCodeCallUnsupported(u16), // 0NNN called a routine of the host CPU, which is not emulated
+    StackOverflow,          // a subroutine call was made with a full stack
+    StackUnderflow,         // a subroutine return was made with an empty stack
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::MemoryOutOfBounds(addr) => {
+                write!(f, "memory access out of bounds at {:#x?}", addr)
+            }
+            Chip8Error::ProgramCounterOutOfBounds(pc) => {
+                write!(f, "program counter out of bounds at {:#x?}", pc)
+            }
+            Chip8Error::UnknownOpcode(opcode) => write!(f, "unknown opcode {:#06x}", opcode),
+            Chip8Error::MachineCodeCallUnsupported(addr) => write!(
+                f,
+                "call to a machine code routine at {:#05x} (0NNN), which only runs on the original hardware",
+                addr
+            ),
+            Chip8Error::StackOverflow => write!(f, "stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "stack underflow"),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// Errors that can be raised when loading a ROM into memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    RomTooLarge { size: usize, available: usize }, // the ROM does not fit in memory
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::RomTooLarge { size, available } => write!(
+                f,
+                "ROM is {} bytes but only {} bytes are available ({} bytes too large)",
+                size,
+                available,
+                size - available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Errors that can be raised when restoring a save state
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    Corrupt(String),            // the bytes could not be decoded
+    Incompatible(&'static str), // the save state does not match this CPU
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Corrupt(reason) => write!(f, "corrupt save state: {}", reason),
+            StateError::Incompatible(part) => {
+                write!(f, "incompatible save state: unexpected {}", part)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for StateError {}
+
+/// Errors that can be raised when extracting a ROM from a zip archive
+#[cfg(feature = "zip")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveError {
+    Unreadable(String),       // the archive could not be opened or decompressed
+    NoRom,                    // the archive does not contain any .ch8 file
+    SeveralRoms(Vec<String>), // the archive contains several .ch8 files and none was picked
+    MissingEntry(String),     // the picked entry is not in the archive
+}
+
+#[cfg(feature = "zip")]
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Unreadable(reason) => write!(f, "unreadable zip archive: {}", reason),
+            ArchiveError::NoRom => write!(f, "the archive does not contain any .ch8 ROM"),
+            ArchiveError::SeveralRoms(names) => {
+                write!(f, "the archive contains several ROMs: {}", names.join(", "))
+            }
+            ArchiveError::MissingEntry(name) => write!(f, "the archive does not contain {}", name),
+        }
+    }
+}
+
+#[cfg(feature = "zip")]
+impl std::error::Error for ArchiveError {}
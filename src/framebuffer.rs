@@ -0,0 +1,118 @@
+pub const DISPLAY_WIDTH: usize = 64; // Default display width
+pub const DISPLAY_HEIGHT: usize = 32; // Default pixel height
+pub const HIRES_WIDTH: usize = 128; // SUPER-CHIP high resolution display width
+pub const HIRES_HEIGHT: usize = 64; // SUPER-CHIP high resolution display height
+pub const PLANE_1: u8 = 0b01; // first bitplane, the only one used by CHIP-8 and SUPER-CHIP
+pub const PLANE_2: u8 = 0b10; // second XO-CHIP bitplane
+pub const ALL_PLANES: u8 = PLANE_1 | PLANE_2;
+
+/// The CHIP-8 screen.
+/// Pixels are addressed with `x` as the column and `y` as the row, so call sites
+/// never have to deal with the row-major `[y][x]` storage order.
+/// The buffer is large enough for the 128x64 SUPER-CHIP mode, only the top-left
+/// `width` x `height` area is in use at the standard 64x32 resolution.
+/// Each pixel holds one bit per XO-CHIP bitplane, giving a value from 0 to 3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer {
+    pixels: [[u8; HIRES_WIDTH]; HIRES_HEIGHT], // stored row by row, i.e. pixels[y][x]
+    width: usize,                              // active width
+    height: usize,                             // active height
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self {
+            pixels: [[0; HIRES_WIDTH]; HIRES_HEIGHT], // screen starts black
+            width: DISPLAY_WIDTH,
+            height: DISPLAY_HEIGHT,
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Switch between the standard 64x32 and the 128x64 high resolution, clearing the screen
+    pub fn set_hi_res(&mut self, hi_res: bool) {
+        (self.width, self.height) = if hi_res {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        };
+        self.clear();
+    }
+
+    /// Check if the pixel at column `x` and row `y` is lit on any plane
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y][x] != 0
+    }
+
+    /// Set the pixel at column `x` and row `y` on the first plane
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        self.pixels[y][x] = (self.pixels[y][x] & !PLANE_1) | on as u8;
+    }
+
+    /// Get the planes lit at column `x` and row `y`, as a value from 0 to 3
+    pub fn planes(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y][x]
+    }
+
+    /// Set the planes lit at column `x` and row `y`
+    pub fn set_planes(&mut self, x: usize, y: usize, planes: u8) {
+        self.pixels[y][x] = planes & ALL_PLANES;
+    }
+
+    /// Scroll the selected planes down by `n` rows, the rows at the top are left blank
+    pub fn scroll_down(&mut self, n: usize, planes: u8) {
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let moved = if y >= n { self.pixels[y - n][x] } else { 0 };
+                self.pixels[y][x] = (self.pixels[y][x] & !planes) | (moved & planes);
+            }
+        }
+    }
+
+    /// Scroll the selected planes right by `n` columns, the columns on the left are left blank
+    pub fn scroll_right(&mut self, n: usize, planes: u8) {
+        for row in self.pixels.iter_mut() {
+            for x in (0..self.width).rev() {
+                let moved = if x >= n { row[x - n] } else { 0 };
+                row[x] = (row[x] & !planes) | (moved & planes);
+            }
+        }
+    }
+
+    /// Scroll the selected planes left by `n` columns, the columns on the right are left blank
+    pub fn scroll_left(&mut self, n: usize, planes: u8) {
+        for row in self.pixels.iter_mut() {
+            for x in 0..self.width {
+                let moved = if x + n < self.width { row[x + n] } else { 0 };
+                row[x] = (row[x] & !planes) | (moved & planes);
+            }
+        }
+    }
+
+    /// Turn off every pixel of the selected planes
+    pub fn clear_planes(&mut self, planes: u8) {
+        for row in self.pixels.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel &= !planes;
+            }
+        }
+    }
+
+    /// Turn every pixel off
+    pub fn clear(&mut self) {
+        self.pixels = [[0; HIRES_WIDTH]; HIRES_HEIGHT];
+    }
+}
@@ -0,0 +1,74 @@
+use sdl2::keyboard::Keycode;
+use std::collections::HashMap;
+
+/// Maps host keyboard keys to the 16 keys of the CHIP-8 hexadecimal keypad.
+/// The layouts are positional: the keypad always sits on the same 4x4 block
+/// of keys, whatever letters are printed on them.
+pub struct Keymap {
+    keys: HashMap<Keycode, usize>,
+}
+
+impl Keymap {
+    /// Build a keymap from the 4x4 block of host keys, listed row by row
+    fn from_rows(rows: [[Keycode; 4]; 4]) -> Self {
+        // CHIP-8 keypad layout, row by row
+        const KEYPAD: [[usize; 4]; 4] = [
+            [0x1, 0x2, 0x3, 0xC],
+            [0x4, 0x5, 0x6, 0xD],
+            [0x7, 0x8, 0x9, 0xE],
+            [0xA, 0x0, 0xB, 0xF],
+        ];
+
+        let keys = rows
+            .iter()
+            .flatten()
+            .zip(KEYPAD.iter().flatten())
+            .map(|(host_key, chip8_key)| (*host_key, *chip8_key))
+            .collect();
+        Self { keys }
+    }
+
+    /// Keypad on the `1234`/`QWER`/`ASDF`/`ZXCV` block
+    pub fn qwerty() -> Self {
+        Self::from_rows([
+            [
+                Keycode::NUM_1,
+                Keycode::NUM_2,
+                Keycode::NUM_3,
+                Keycode::NUM_4,
+            ],
+            [Keycode::Q, Keycode::W, Keycode::E, Keycode::R],
+            [Keycode::A, Keycode::S, Keycode::D, Keycode::F],
+            [Keycode::Z, Keycode::X, Keycode::C, Keycode::V],
+        ])
+    }
+
+    /// Keypad on the `&é"'`/`AZER`/`QSDF`/`WXCV` block
+    pub fn azerty() -> Self {
+        Self::from_rows([
+            [
+                Keycode::NUM_1,
+                Keycode::NUM_2,
+                Keycode::NUM_3,
+                Keycode::NUM_4,
+            ],
+            [Keycode::A, Keycode::Z, Keycode::E, Keycode::R],
+            [Keycode::Q, Keycode::S, Keycode::D, Keycode::F],
+            [Keycode::W, Keycode::X, Keycode::C, Keycode::V],
+        ])
+    }
+
+    /// Get a keymap by layout name (`qwerty` or `azerty`)
+    pub fn from_layout(layout: &str) -> Option<Self> {
+        match layout.to_lowercase().as_str() {
+            "qwerty" => Some(Self::qwerty()),
+            "azerty" => Some(Self::azerty()),
+            _ => None,
+        }
+    }
+
+    /// Get the CHIP-8 key for a host key, if it is part of the keypad
+    pub fn map(&self, key: Keycode) -> Option<usize> {
+        self.keys.get(&key).copied()
+    }
+}
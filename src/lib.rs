@@ -1,10 +1,50 @@
+#[cfg(feature = "zip")]
+pub mod archive;
+#[cfg(feature = "sdl")]
 pub mod audio;
+pub mod builder;
 pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+#[cfg(feature = "sdl")]
 pub mod display;
+pub mod error;
+pub mod framebuffer;
+#[cfg(feature = "sdl")]
+pub mod keymap;
 pub mod opcode;
+pub mod quirks;
+pub mod recording;
+pub mod renderer;
+pub mod rom;
+#[cfg(feature = "image")]
+pub mod screenshot;
+pub mod stats;
+pub mod terminal;
+pub mod timing;
 
 // public re-export
+#[cfg(feature = "zip")]
+pub use archive::*;
+#[cfg(feature = "sdl")]
 pub use audio::*;
+pub use builder::*;
 pub use cpu::*;
+pub use debugger::*;
+pub use disasm::*;
+#[cfg(feature = "sdl")]
 pub use display::*;
+pub use error::*;
+pub use framebuffer::*;
+#[cfg(feature = "sdl")]
+pub use keymap::*;
 pub use opcode::*;
+pub use quirks::*;
+pub use recording::*;
+pub use renderer::*;
+pub use rom::*;
+#[cfg(feature = "image")]
+pub use screenshot::*;
+pub use stats::*;
+pub use terminal::*;
+pub use timing::*;
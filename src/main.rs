@@ -1,16 +1,59 @@
-use chip_8::{initialize_audio, Chip8, Display};
-use clap::{Arg, ArgMatches, Command};
-use sdl2::event::Event;
+#[cfg(unix)]
+use chip_8::TerminalInput;
+use chip_8::{
+    identify_rom, initialize_audio, is_valid_audio_buffer_size, is_valid_beep_frequency,
+    load_histogram, mnemonic, precise_wait, save_histogram, update_audio_pattern, update_sound,
+    Chip8, Chip8Error, DebugCommand, Display, FrameLimiter, HeldKeys, Keymap, Quirks, Recorder,
+    Renderer, TerminalDisplay, TerminalKeymap, Waveform, BUILTIN_ROM, BUILTIN_ROM_NAME,
+    DEFAULT_MEMORY_SIZE, MAX_AUDIO_BUFFER, MIN_AUDIO_BUFFER, SAMPLE_RATE,
+};
+#[cfg(feature = "zip")]
+use chip_8::{is_zip_archive, read_zipped_rom, ArchiveError};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
-use std::path::Path;
+use sdl2::pixels::Color;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-const RUN_FREQUENCY: u64 = 700; // 700 Chip-8 instructions per second
-const RUN_INTERVAL: Duration = Duration::from_micros(1_000_000 / RUN_FREQUENCY); // should cycle 700 instructions per second
+// Settings chosen on the command line
+struct Settings {
+    rom_name: String,
+    scale: u32,
+    fps: u32,
+    trace_draws: bool,
+    trace_file: Option<PathBuf>,
+    debug: bool,
+    break_opcode: Option<u16>,
+    breakpoints: Vec<u16>,
+    watchpoints: Vec<u16>,
+    program_start: u16,
+    quirks: Option<Quirks>,
+    seed: Option<u64>,
+    audio_buffer: u16,
+    waveform: Waveform,
+    beep_frequency: f32,
+    volume: f32,
+    pan: f32,
+    stats_file: Option<PathBuf>,
+    record_file: Option<PathBuf>,
+    replay_file: Option<PathBuf>,
+    layout: String,
+    background: Color,
+    foreground: Color,
+    palette: Option<[Color; 4]>,
+    terminal: bool,
+    diff_draw: bool,
+    show_stats: bool,
+    #[cfg(feature = "zip")]
+    entry: Option<String>,
+}
 
 fn main() {
     // Getting CLI info
-    let matches = Command::new("CHIP-8 emulator")
+    let command = Command::new("CHIP-8 emulator")
         .version("0.1")
         .author("Oghenemarho ORUKELE <orukele.dev@gmail.com>")
         .about("A CHIP-8 emulator written in Rust")
@@ -18,7 +61,7 @@ fn main() {
             Arg::new("ROM")
                 .short('r')
                 .long("rom")
-                .help("Name of CHIP-8 ROM file (remember to specify the extension)")
+                .help("Path or name (in ./rom/) of the CHIP-8 ROM file, or \"builtin\"")
                 .required(true)
                 .default_value("1-chip8-logo"),
         )
@@ -26,113 +69,966 @@ fn main() {
             Arg::new("scale")
                 .short('s')
                 .help("Set the scale of the display")
+                .value_parser(value_parser!(u32))
                 .required(false)
                 .default_value("10")
                 .default_missing_value("10"),
         )
-        .get_matches();
+        .arg(
+            Arg::new("fps")
+                .long("fps")
+                .help("Maximum number of frames drawn per second")
+                .value_parser(value_parser!(u32))
+                .required(false)
+                .default_value("60"),
+        )
+        .arg(
+            Arg::new("trace-draws")
+                .long("trace-draws")
+                .help("Log every sprite draw (DXYN) to stdout")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("trace-file")
+                .long("trace-file")
+                .help("File where the address, opcode and mnemonic of every executed instruction are appended")
+                .required(false),
+        )
+        .arg(
+            Arg::new("debug")
+                .long("debug")
+                .help("Enable debugging features")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("break-opcode")
+                .long("break-opcode")
+                .help("Opcode (in hex, e.g. 0xF0FF) that breaks into the debugger when executed")
+                .value_parser(parse_hex)
+                .required(false),
+        )
+        .arg(
+            Arg::new("breakpoint")
+                .long("breakpoint")
+                .help("Address (in hex) where execution breaks into the debugger, can be repeated")
+                .value_parser(parse_hex)
+                .required(false)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("watchpoint")
+                .long("watchpoint")
+                .help("Address (in hex) whose reads and writes break into the debugger, can be repeated")
+                .value_parser(parse_hex)
+                .required(false)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("start-address")
+                .long("start-address")
+                .help("Address (in hex) where the ROM is loaded and starts, e.g. 0x600 for ETI-660 programs")
+                .value_parser(parse_start_address)
+                .required(false)
+                .default_value("0x200"),
+        )
+        .arg(
+            Arg::new("quirks")
+                .long("quirks")
+                .help("Interpreter to emulate (cosmac, superchip or xochip), detected from the ROM when not given")
+                .value_parser(parse_quirks)
+                .required(false),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Seed of the random numbers, to get the same ones on every run")
+                .value_parser(value_parser!(u64))
+                .required(false),
+        )
+        .arg(
+            Arg::new("audio-buffer")
+                .long("audio-buffer")
+                .help("Audio buffer size in samples (power of two); lower values reduce latency but may crackle")
+                .value_parser(parse_audio_buffer)
+                .required(false)
+                .default_value("512"),
+        )
+        .arg(
+            Arg::new("waveform")
+                .long("waveform")
+                .help("Shape of the beep sound (square, sine, triangle or sawtooth)")
+                .value_parser(parse_waveform)
+                .required(false)
+                .default_value("square"),
+        )
+        .arg(
+            Arg::new("beep-freq")
+                .long("beep-freq")
+                .help("Pitch of the beep in Hz")
+                .value_parser(parse_beep_frequency)
+                .required(false)
+                .default_value("440"),
+        )
+        .arg(
+            Arg::new("volume")
+                .long("volume")
+                .help("Volume of the beep, from 0.0 to 1.0")
+                .value_parser(parse_volume)
+                .required(false)
+                .default_value("0.25"),
+        )
+        .arg(
+            Arg::new("pan")
+                .long("pan")
+                .help("Position of the beep, from -1.0 (left) to 1.0 (right)")
+                .value_parser(parse_pan)
+                .required(false)
+                .allow_hyphen_values(true)
+                .default_value("0.0"),
+        )
+        .arg(
+            Arg::new("stats-file")
+                .long("stats-file")
+                .help("File where the opcode histogram is accumulated across runs")
+                .required(false),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .help("File where the keypad input is saved, to replay the session with --replay")
+                .required(false),
+        )
+        .arg(
+            Arg::new("replay")
+                .long("replay")
+                .help("Play back the keypad input saved with --record")
+                .required(false)
+                .conflicts_with("record"),
+        )
+        .arg(
+            Arg::new("layout")
+                .long("layout")
+                .help("Keyboard layout used for the keypad (qwerty or azerty)")
+                .required(false)
+                .default_value("azerty"),
+        )
+        .arg(
+            Arg::new("terminal")
+                .long("terminal")
+                .help("Run in the terminal, drawing with text and reading the keypad from it, without SDL")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("diff-draw")
+                .long("diff-draw")
+                .help("Only redraw the pixels that changed since the last frame")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("show-stats")
+                .long("show-stats")
+                .help("Show the measured frames and instructions per second in the top-left corner")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bg")
+                .long("bg")
+                .help("Background color as a hex RGB value, e.g. #000000")
+                .value_parser(parse_color)
+                .required(false)
+                .default_value("#000000"),
+        )
+        .arg(
+            Arg::new("fg")
+                .long("fg")
+                .help("Foreground color of lit pixels as a hex RGB value, e.g. #00FF00")
+                .value_parser(parse_color)
+                .required(false)
+                .default_value("#FFFFFF"),
+        )
+        .arg(
+            Arg::new("palette")
+                .long("palette")
+                .help("Four comma-separated hex RGB colors for the XO-CHIP plane combinations: background, plane 1, plane 2 and both planes; overrides --bg and --fg")
+                .value_parser(parse_palette)
+                .required(false),
+        );
+    #[cfg(feature = "zip")]
+    let command = command.arg(
+        Arg::new("entry")
+            .long("entry")
+            .help("Name of the ROM to run when the ROM is a zip archive holding several")
+            .required(false),
+    );
+    let matches = command.get_matches();
 
     // Extract arguments
-    let (rom_name, scale) = extract_arguments(matches);
+    let settings = extract_arguments(matches);
 
     // Run emulator
-    run_emulator(&rom_name, scale);
+    if let Err(error) = run_emulator(&settings) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 }
 
-fn extract_arguments(matches: ArgMatches) -> (String, u32) {
+fn extract_arguments(matches: ArgMatches) -> Settings {
     let rom_name = matches
         .get_one::<String>("ROM")
         .expect("unable to get ROM name")
         .to_owned();
-    let scale: u32 = matches
-        .get_one::<String>("scale")
-        .expect("unable to get scale factor")
+    let scale = *matches
+        .get_one::<u32>("scale")
+        .expect("unable to get scale factor");
+    let fps = *matches
+        .get_one::<u32>("fps")
+        .expect("unable to get frame rate");
+    let trace_draws = matches.get_flag("trace-draws");
+    let trace_file = matches.get_one::<String>("trace-file").map(PathBuf::from);
+    let debug = matches.get_flag("debug");
+    let break_opcode = matches.get_one::<u16>("break-opcode").copied();
+    let breakpoints = matches
+        .get_many::<u16>("breakpoint")
+        .unwrap_or_default()
+        .copied()
+        .collect();
+    let watchpoints = matches
+        .get_many::<u16>("watchpoint")
+        .unwrap_or_default()
+        .copied()
+        .collect();
+    let program_start = *matches
+        .get_one::<u16>("start-address")
+        .expect("unable to get start address");
+    let quirks = matches.get_one::<Quirks>("quirks").copied();
+    let seed = matches.get_one::<u64>("seed").copied();
+    let audio_buffer = *matches
+        .get_one::<u16>("audio-buffer")
+        .expect("unable to get audio buffer size");
+    let waveform = *matches
+        .get_one::<Waveform>("waveform")
+        .expect("unable to get waveform");
+    let beep_frequency = *matches
+        .get_one::<f32>("beep-freq")
+        .expect("unable to get beep frequency");
+    let volume = *matches
+        .get_one::<f32>("volume")
+        .expect("unable to get volume");
+    let pan = *matches.get_one::<f32>("pan").expect("unable to get pan");
+    let stats_file = matches.get_one::<String>("stats-file").map(PathBuf::from);
+    let record_file = matches.get_one::<String>("record").map(PathBuf::from);
+    let replay_file = matches.get_one::<String>("replay").map(PathBuf::from);
+    let layout = matches
+        .get_one::<String>("layout")
+        .expect("unable to get keyboard layout")
+        .to_owned();
+    let background = *matches
+        .get_one::<Color>("bg")
+        .expect("unable to get background color");
+    let foreground = *matches
+        .get_one::<Color>("fg")
+        .expect("unable to get foreground color");
+    let palette = matches.get_one::<[Color; 4]>("palette").copied();
+    let terminal = matches.get_flag("terminal");
+    let diff_draw = matches.get_flag("diff-draw");
+    let show_stats = matches.get_flag("show-stats");
+    #[cfg(feature = "zip")]
+    let entry = matches.get_one::<String>("entry").cloned();
+
+    Settings {
+        rom_name,
+        scale,
+        fps,
+        trace_draws,
+        trace_file,
+        debug,
+        break_opcode,
+        breakpoints,
+        watchpoints,
+        program_start,
+        quirks,
+        seed,
+        audio_buffer,
+        waveform,
+        beep_frequency,
+        volume,
+        pan,
+        stats_file,
+        record_file,
+        replay_file,
+        layout,
+        background,
+        foreground,
+        palette,
+        terminal,
+        diff_draw,
+        show_stats,
+        #[cfg(feature = "zip")]
+        entry,
+    }
+}
+
+// Parsers of the argument values, a bad value is reported by clap with the usage
+
+fn parse_hex(value: &str) -> Result<u16, String> {
+    // Addresses and opcodes are given in hex, optionally prefixed with 0x
+    u16::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| "expected a hexadecimal number like 0x200".to_string())
+}
+
+fn parse_start_address(value: &str) -> Result<u16, String> {
+    // The emulator runs with the default memory, the ROM has to start inside it
+    let addr = parse_hex(value)?;
+    if (addr as usize) < DEFAULT_MEMORY_SIZE {
+        Ok(addr)
+    } else {
+        Err(format!(
+            "expected an address below {:#05x}",
+            DEFAULT_MEMORY_SIZE
+        ))
+    }
+}
+
+fn parse_quirks(name: &str) -> Result<Quirks, String> {
+    Quirks::from_name(name).ok_or_else(|| "expected cosmac, superchip or xochip".to_string())
+}
+
+fn parse_audio_buffer(samples: &str) -> Result<u16, String> {
+    samples
+        .parse()
+        .ok()
+        .filter(|&samples| is_valid_audio_buffer_size(samples))
+        .ok_or_else(|| {
+            format!(
+                "expected a power of two between {} and {}",
+                MIN_AUDIO_BUFFER, MAX_AUDIO_BUFFER
+            )
+        })
+}
+
+fn parse_beep_frequency(frequency: &str) -> Result<f32, String> {
+    frequency
         .parse()
-        .unwrap_or(10);
-    (rom_name, scale)
+        .ok()
+        .filter(|&frequency| is_valid_beep_frequency(frequency))
+        .ok_or_else(|| {
+            format!(
+                "expected a frequency above 0 and below {} Hz",
+                SAMPLE_RATE / 2
+            )
+        })
+}
+
+fn parse_waveform(name: &str) -> Result<Waveform, String> {
+    Waveform::from_name(name)
+        .ok_or_else(|| "expected square, sine, triangle or sawtooth".to_string())
+}
+
+fn parse_volume(volume: &str) -> Result<f32, String> {
+    volume
+        .parse()
+        .ok()
+        .filter(|volume| (0.0..=1.0).contains(volume))
+        .ok_or_else(|| "expected a number between 0.0 and 1.0".to_string())
+}
+
+fn parse_pan(pan: &str) -> Result<f32, String> {
+    pan.parse()
+        .ok()
+        .filter(|pan| (-1.0..=1.0).contains(pan))
+        .ok_or_else(|| "expected a number between -1.0 and 1.0".to_string())
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    // Colors are given as RRGGBB, optionally prefixed with #
+    let hex = color.trim_start_matches('#');
+    let rgb = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("expected a hex RGB value like #00FF00 instead of {}", color))?;
+    Ok(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+fn parse_palette(palette: &str) -> Result<[Color; 4], String> {
+    let colors = palette
+        .split(',')
+        .map(|color| parse_color(color.trim()))
+        .collect::<Result<Vec<Color>, String>>()?;
+    colors
+        .try_into()
+        .map_err(|_| "expected four colors".to_string())
+}
+
+fn run_emulator(settings: &Settings) -> Result<(), String> {
+    let mut chip8 = create_chip8(settings)?;
+    if settings.terminal {
+        run_in_terminal(settings, &mut chip8)?;
+    } else {
+        run_in_window(settings, &mut chip8)?;
+    }
+    save_session(settings, &mut chip8);
+    Ok(())
+}
+
+fn create_chip8(settings: &Settings) -> Result<Chip8, String> {
+    let rom = read_rom(settings)?;
+
+    // Pick the quirks asked for, or those of the interpreter the ROM was written for when it is known
+    let quirks = match (settings.quirks, identify_rom(&rom)) {
+        (Some(quirks), _) => quirks,
+        (None, Some(known)) => {
+            println!("Detected {}, applying {} quirks", known.title, known.preset);
+            (known.quirks)()
+        }
+        (None, None) => Quirks::cosmac_vip(),
+    };
+
+    // create new instance of Chip-8
+    let mut builder = Chip8::builder()
+        .quirks(quirks)
+        .program_start(settings.program_start);
+    if let Some(seed) = settings.seed {
+        builder = builder.seed(seed);
+    }
+    let mut chip8 = builder.build();
+    if settings.trace_draws {
+        chip8.set_draw_trace_hook(Box::new(|draw| {
+            let sprite: Vec<String> = draw
+                .sprite
+                .iter()
+                .map(|byte| format!("{:#04x}", byte))
+                .collect();
+            println!(
+                "Draw trace: sprite [{}] at ({}, {}) - {} pixels set, {} pixels cleared, VF = {}",
+                sprite.join(", "),
+                draw.x,
+                draw.y,
+                draw.pixels_set,
+                draw.pixels_cleared,
+                draw.collision as u8
+            );
+        }));
+    }
+    if let Some(trace_file) = &settings.trace_file {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(trace_file)
+            .map_err(|error| {
+                format!(
+                    "Unable to open trace file {}: {}",
+                    trace_file.display(),
+                    error
+                )
+            })?;
+        // buffered so that tracing does not slow the emulation down, flushed when the CPU is dropped
+        let mut writer = BufWriter::new(file);
+        chip8.set_trace_hook(Box::new(move |address, opcode, description| {
+            let mnemonic = mnemonic(opcode).unwrap_or_else(|| format!("DW 0x{:04X}", opcode));
+            // a failing write only loses trace lines, the game keeps running
+            let _ = writeln!(
+                writer,
+                "{:#05x}  {:04X}  {:<20} {}",
+                address, opcode, mnemonic, description
+            );
+        }));
+    }
+    chip8.set_debug(settings.debug);
+    chip8.set_break_opcode(settings.break_opcode);
+    for &addr in &settings.breakpoints {
+        chip8.add_breakpoint(addr);
+    }
+    for &addr in &settings.watchpoints {
+        chip8.add_watchpoint(addr);
+    }
+    chip8
+        .load_rom(rom)
+        .map_err(|error| format!("Unable to load ROM {}: {}", settings.rom_name, error))?;
+    if settings.record_file.is_some() {
+        chip8.start_recording();
+    }
+    if let Some(replay_file) = &settings.replay_file {
+        let recording = Recorder::load(replay_file).map_err(|error| {
+            format!(
+                "Unable to read recording {}: {}",
+                replay_file.display(),
+                error
+            )
+        })?;
+        chip8.start_replay(&recording);
+    }
+    if let Some(stats_file) = &settings.stats_file {
+        chip8.merge_opcode_histogram(&load_histogram(stats_file)); // carry on from previous runs
+    }
+
+    Ok(chip8)
 }
 
-fn run_emulator(rom_name: &String, scale: u32) {
-    let sdl_context = sdl2::init().unwrap();
-    let mut display = Display::new(&sdl_context, scale);
+fn save_session(settings: &Settings, chip8: &mut Chip8) {
+    if let (Some(record_file), Some(recording)) = (&settings.record_file, chip8.stop_recording()) {
+        if let Err(error) = recording.save(record_file) {
+            eprintln!("Unable to save recording: {}", error);
+        }
+    }
+    if let Some(stats_file) = &settings.stats_file {
+        if let Err(error) = save_histogram(stats_file, chip8.opcode_histogram()) {
+            eprintln!("Unable to save opcode statistics: {}", error);
+        }
+    }
+}
 
-    let rom_path = format!("./rom/{}", rom_name);
-    let path = Path::new(&rom_path);
-    let rom = std::fs::read(path).expect("Unable to read file");
+fn run_in_window(settings: &Settings, chip8: &mut Chip8) -> Result<(), String> {
+    let Some(keymap) = Keymap::from_layout(&settings.layout) else {
+        return Err(format!("Unknown keyboard layout: {}", settings.layout));
+    };
 
-    let mut chip8 = Chip8::new(false); // create new instance of Chip-8
-    chip8.load_rom(rom); // load rom
+    let sdl_context =
+        sdl2::init().map_err(|error| format!("Could not initialize SDL: {}", error))?;
+    let mut renderer = Display::new(&sdl_context, settings.scale)
+        .map_err(|error| format!("Could not initialize display: {}", error))?;
+    renderer.set_colors(settings.background, settings.foreground);
+    if let Some(palette) = settings.palette {
+        renderer.set_palette(palette);
+    }
+    renderer.set_diff_draw(settings.diff_draw);
 
-    let (audio_device, is_playing) = initialize_audio(); // initialize audio with SDL2
+    let (mut audio_device, is_playing) = initialize_audio(
+        &sdl_context,
+        settings.audio_buffer,
+        settings.waveform,
+        settings.beep_frequency,
+        settings.volume,
+        settings.pan,
+    )
+    .map_err(|error| format!("Could not initialize audio: {}", error))?; // initialize audio with SDL2
 
+    let run_interval = Duration::from_micros(1_000_000 / chip8.clock_speed()); // time between two instructions
     let mut start = Instant::now(); // set up timer to ensure run of 700 instruction per second
+    let mut frame_limiter = FrameLimiter::new(settings.fps); // caps the display refresh rate
+    let mut stats_start = Instant::now(); // start of the period the shown rates are measured over
+    let mut stats_frames = 0; // frames since stats_start
+    let mut stats_instructions = chip8.instruction_count(); // instruction count at stats_start
+
+    let mut paused = false; // toggled with the pause key, the CPU and timers are frozen while set
+    let mut audio_pattern = None; // XO-CHIP audio pattern and pitch last given to the audio device
+    let mut turbo = false; // set while the turbo key is held, frames are run back to back
+    let cycles_per_frame = (chip8.clock_speed() / chip8.timer_frequency()).max(1) as usize;
 
     // main loop
-    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut event_pump = sdl_context
+        .event_pump()
+        .map_err(|error| format!("Could not initialize input: {}", error))?;
     'running: loop {
         for event in event_pump.poll_iter() {
             match event {
                 Event::KeyDown {
                     keycode: Some(key), ..
                 } => {
-                    if let Some(chip8_key) = map_key(key) {
-                        chip8.keypad[chip8_key] = true; // Set key pressed to true
+                    if let Some(chip8_key) = keymap.map(key).filter(|_| !chip8.is_replaying()) {
+                        chip8.set_key(chip8_key as u8, true); // Set key pressed to true
                     }
 
                     // Check escape key
                     if key == Keycode::ESCAPE {
                         break 'running;
                     }
+
+                    // Restart the game
+                    if key == Keycode::F5 {
+                        chip8.reset(true);
+                    }
+
+                    // Save the screen to a PNG file
+                    #[cfg(feature = "image")]
+                    if key == Keycode::F12 {
+                        take_screenshot(chip8, settings.scale);
+                    }
+
+                    // Pause or resume the game
+                    if key == Keycode::P {
+                        paused = !paused;
+                        if paused {
+                            update_sound(&audio_device, &is_playing, false); // silence the beep
+                            renderer.draw_paused(&chip8.display); // dim the last frame
+                        } else {
+                            renderer.draw(&chip8.display);
+                        }
+                    }
+
+                    // Execute a single instruction while paused
+                    if key == Keycode::N && paused {
+                        if let Err(error) = run_cycle(chip8, debug_prompt) {
+                            eprintln!("Emulation stopped: {}\n{}", error, chip8.dump_state());
+                            break 'running;
+                        }
+                        println!("PC: {:#05x}", chip8.program_counter());
+                    }
+
+                    // Print the CPU state
+                    if key == Keycode::I {
+                        println!("{}", chip8.dump_state());
+                    }
+
+                    // Switch between windowed and fullscreen
+                    if key == Keycode::F11 {
+                        renderer.toggle_fullscreen();
+                    }
+
+                    // Fast-forward while held
+                    if key == Keycode::TAB {
+                        turbo = true;
+                    }
                 }
                 Event::KeyUp {
                     keycode: Some(key), ..
                 } => {
-                    if let Some(chip8_key) = map_key(key) {
-                        chip8.keypad[chip8_key] = false; // Set key unpressed to false
+                    if let Some(chip8_key) = keymap.map(key).filter(|_| !chip8.is_replaying()) {
+                        chip8.set_key(chip8_key as u8, false); // Set key unpressed to false
+                    }
+
+                    if key == Keycode::TAB {
+                        turbo = false;
+                    }
+                }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(width, height),
+                    ..
+                } => {
+                    renderer.resize(width as u32, height as u32);
+                    // repaint at once, even when paused
+                    if paused {
+                        renderer.draw_paused(&chip8.display);
+                    } else {
+                        renderer.draw(&chip8.display);
                     }
                 }
                 Event::Quit { .. } => {
-                    std::process::exit(0); // Exit on quit event
+                    break 'running; // Exit on quit event
                 }
                 _ => {}
             }
         }
 
         let elapsed_time = start.elapsed(); // get the time elapsed
-        if elapsed_time >= RUN_INTERVAL {
+        if paused {
+            // keep polling events and showing the last frame without running the CPU
+            precise_wait(run_interval);
+        } else if chip8.is_halted() {
+            // the ROM ended in an infinite loop, only let the timers run out
+            chip8.update_timers();
+            update_sound(&audio_device, &is_playing, chip8.is_beeping());
+            precise_wait(run_interval);
+        } else if turbo {
+            // run whole frames, timers included, as fast as the host allows
+            if let Err(error) = chip8.run_frame(cycles_per_frame) {
+                eprintln!("Emulation stopped: {}\n{}", error, chip8.dump_state());
+                break 'running;
+            }
+            while chip8.take_break_request() {
+                debug_prompt(chip8);
+            }
+            update_sound(&audio_device, &is_playing, chip8.is_beeping());
+            start = Instant::now();
+        } else if elapsed_time >= run_interval {
             // check if elapsed time is greater than run interval
-            chip8.cycle(); // chip 8 cycle here
-            display.draw(&chip8.display); // render the CHIP-8 display
-            chip8.update_sound(&audio_device, &is_playing);
-            chip8.update_timers(); // update timers
+            // chip 8 cycle here
+            if let Err(error) = run_cycle(chip8, debug_prompt) {
+                eprintln!("Emulation stopped: {}\n{}", error, chip8.dump_state());
+                break 'running;
+            }
+            update_sound(&audio_device, &is_playing, chip8.is_beeping());
             start = Instant::now(); // update the run timer to now
         } else {
             // This is to prevent Busy-Wait loop.
-            std::thread::sleep(RUN_INTERVAL - elapsed_time);
-        }
-    }
-}
-
-fn map_key(key: Keycode) -> Option<usize> {
-    // Map host keyboard keys to CHIP-8 keys
-    // NOTE: This works for 'AZERTY' keyboard only
-    match key {
-        Keycode::NUM_1 => Some(0x1),
-        Keycode::NUM_2 => Some(0x2),
-        Keycode::NUM_3 => Some(0x3),
-        Keycode::NUM_4 => Some(0xC),
-        Keycode::A => Some(0x4),
-        Keycode::Z => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-        Keycode::Q => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-        Keycode::W => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None, // Ignore other keys
+            precise_wait(run_interval - elapsed_time);
+        }
+
+        // XO-CHIP games play their own audio pattern instead of the beep
+        let pattern = (*chip8.audio_pattern(), chip8.pitch());
+        if chip8.quirks().xo_chip && audio_pattern != Some(pattern) {
+            audio_pattern = Some(pattern);
+            update_audio_pattern(
+                &mut audio_device,
+                chip8.audio_pattern(),
+                chip8.audio_playback_rate(),
+            );
+        }
+
+        if frame_limiter.try_begin_frame(Instant::now()) {
+            let mut redraw = chip8.take_display_dirty();
+
+            // refresh the measured rates every second
+            if settings.show_stats {
+                stats_frames += 1;
+                let elapsed = stats_start.elapsed();
+                if elapsed >= Duration::from_secs(1) {
+                    let seconds = elapsed.as_secs_f64();
+                    let instructions = chip8.instruction_count() - stats_instructions;
+                    renderer.set_stats(
+                        (stats_frames as f64 / seconds).round() as u32,
+                        (instructions as f64 / seconds).round() as u64,
+                    );
+                    stats_start = Instant::now();
+                    stats_frames = 0;
+                    stats_instructions = chip8.instruction_count();
+                    redraw = true;
+                }
+            }
+
+            // render the CHIP-8 display only when it changed
+            if redraw {
+                if paused {
+                    renderer.draw_paused(&chip8.display); // stepped while paused
+                } else {
+                    renderer.draw(&chip8.display);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_in_terminal(settings: &Settings, chip8: &mut Chip8) -> Result<(), String> {
+    let Some(keymap) = TerminalKeymap::from_layout(&settings.layout) else {
+        return Err(format!("Unknown keyboard layout: {}", settings.layout));
+    };
+    let mut renderer = TerminalDisplay::new();
+
+    // read the keypad from the terminal, the game still runs without it when stdin is not a terminal
+    #[cfg(unix)]
+    let mut input = match TerminalInput::new() {
+        Ok(input) => Some(input),
+        Err(error) => {
+            eprintln!(
+                "Unable to read keys from the terminal ({}), running without input",
+                error
+            );
+            None
+        }
+    };
+    #[cfg(not(unix))]
+    let input: Option<()> = None;
+    let mut held_keys = HeldKeys::new(); // keypad keys typed recently, the terminal sends no releases
+
+    let run_interval = Duration::from_micros(1_000_000 / chip8.clock_speed()); // time between two instructions
+    let mut start = Instant::now(); // time the last instruction ran
+    let mut frame_limiter = FrameLimiter::new(settings.fps); // caps the display refresh rate
+    let mut paused = false; // toggled with the pause key, the CPU and timers are frozen while set
+    let mut beeping = false; // the bell rings once each time the beep starts
+
+    'running: loop {
+        #[cfg(unix)]
+        let typed = input.as_mut().map(TerminalInput::read).unwrap_or_default();
+        #[cfg(not(unix))]
+        let typed: Vec<u8> = Vec::new();
+        let now = Instant::now();
+        for (i, &byte) in typed.iter().enumerate() {
+            match byte {
+                // Ctrl+C, or Escape on its own rather than the start of an escape sequence
+                0x03 => break 'running,
+                0x1B if i + 1 == typed.len() => break 'running,
+                // Pause or resume the game
+                b'p' => {
+                    paused = !paused;
+                    if paused {
+                        renderer.draw_paused(&chip8.display); // dim the last frame
+                    } else {
+                        renderer.draw(&chip8.display);
+                    }
+                }
+                // Execute a single instruction while paused
+                b'n' if paused => {
+                    if let Err(error) =
+                        run_cycle(chip8, |chip8| prompt_in_cooked_mode(chip8, &input))
+                    {
+                        eprintln!("Emulation stopped: {}\n{}", error, chip8.dump_state());
+                        break 'running;
+                    }
+                    println!("PC: {:#05x}", chip8.program_counter());
+                }
+                // Print the CPU state
+                b'i' => println!("{}", chip8.dump_state()),
+                _ => {
+                    if let Some(chip8_key) =
+                        keymap.map(byte as char).filter(|_| !chip8.is_replaying())
+                    {
+                        if held_keys.press(chip8_key, now) {
+                            chip8.set_key(chip8_key, true);
+                        }
+                    }
+                }
+            }
+        }
+        for chip8_key in held_keys.release_expired(now) {
+            if !chip8.is_replaying() {
+                chip8.set_key(chip8_key, false);
+            }
+        }
+
+        let elapsed_time = start.elapsed();
+        if paused {
+            precise_wait(run_interval);
+        } else if chip8.is_halted() {
+            // the ROM ended in an infinite loop, only let the timers run out
+            chip8.update_timers();
+            precise_wait(run_interval);
+        } else if elapsed_time >= run_interval {
+            if let Err(error) = run_cycle(chip8, |chip8| prompt_in_cooked_mode(chip8, &input)) {
+                eprintln!("Emulation stopped: {}\n{}", error, chip8.dump_state());
+                break 'running;
+            }
+            start = Instant::now();
+        } else {
+            precise_wait(run_interval - elapsed_time);
+        }
+
+        // ring the terminal bell in place of the beep
+        if chip8.is_beeping() && !beeping {
+            print!("\x07");
+            let _ = std::io::stdout().flush();
+        }
+        beeping = chip8.is_beeping();
+
+        if frame_limiter.try_begin_frame(Instant::now()) && chip8.take_display_dirty() {
+            if paused {
+                renderer.draw_paused(&chip8.display); // stepped while paused
+            } else {
+                renderer.draw(&chip8.display);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Function to run one instruction with the timers, as the main loop and single-stepping do,
+/// waiting for the vertical blank after a draw when the display_wait quirk asks for it
+/// and handing the breaks requested by the instruction to `prompt`
+fn run_cycle(chip8: &mut Chip8, mut prompt: impl FnMut(&mut Chip8)) -> Result<(), Chip8Error> {
+    let drew = chip8.cycle()?;
+    if drew && chip8.quirks().display_wait {
+        // the CPU is idle until the vertical blank, no need to spin
+        precise_wait(chip8.time_until_timer_tick());
+    }
+    while chip8.take_break_request() {
+        prompt(chip8);
+    }
+    chip8.update_timers();
+    Ok(())
+}
+
+/// Function to show the debugger prompt from the terminal mode, which reads whole lines
+/// with echo, so the terminal leaves raw mode while it is shown
+#[cfg(unix)]
+fn prompt_in_cooked_mode(chip8: &mut Chip8, input: &Option<TerminalInput>) {
+    if let Some(input) = input {
+        let _ = input.restore_mode();
+    }
+    debug_prompt(chip8);
+    if let Some(input) = input {
+        let _ = input.enable_raw_mode();
+    }
+}
+
+#[cfg(not(unix))]
+fn prompt_in_cooked_mode(chip8: &mut Chip8, _input: &Option<()>) {
+    debug_prompt(chip8);
+}
+
+fn read_rom(settings: &Settings) -> Result<Vec<u8>, String> {
+    let rom_name = &settings.rom_name;
+    if rom_name == BUILTIN_ROM_NAME {
+        return Ok(BUILTIN_ROM.to_vec());
+    }
+
+    // Use the path as given, and only look in ./rom/ if there is no such file
+    let mut rom_path = PathBuf::from(rom_name);
+    if !rom_path.exists() {
+        rom_path = Path::new("./rom").join(rom_name);
+    }
+
+    // A ROM collection, the ROM to run has to be picked out of it
+    #[cfg(feature = "zip")]
+    if is_zip_archive(&rom_path) {
+        return read_zipped_rom(&rom_path, settings.entry.as_deref()).map_err(
+            |error| match error {
+                ArchiveError::SeveralRoms(_) => format!("{}, pick one with --entry", error),
+                _ => format!("Unable to read {}: {}", rom_path.display(), error),
+            },
+        );
+    }
+
+    match std::fs::read(&rom_path) {
+        Ok(rom) => Ok(rom),
+        Err(error) => {
+            // Fall back to the embedded ROM so the emulator is usable out of the box
+            eprintln!(
+                "Unable to read {} ({}), running the builtin ROM instead",
+                rom_path.display(),
+                error
+            );
+            Ok(BUILTIN_ROM.to_vec())
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+fn take_screenshot(chip8: &Chip8, scale: u32) {
+    // Name the file after the current time so screenshots never overwrite each other
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let path = PathBuf::from(format!("screenshot-{}.png", timestamp));
+    match chip_8::save_screenshot(chip8.display_buffer(), scale, &path) {
+        Ok(()) => println!("Screenshot saved to {}", path.display()),
+        Err(error) => eprintln!("Unable to save screenshot: {}", error),
+    }
+}
+
+fn debug_prompt(chip8: &mut Chip8) {
+    // Block until the user resumes from the terminal
+    for hit in chip8.take_watch_hits() {
+        println!(
+            "Watchpoint {:#05x}: {:?} by the instruction at {:#05x}",
+            hit.addr, hit.access, hit.instruction
+        );
+    }
+    println!("Break reached at {:#05x} (continue | step [N] | run-to <addr> | break <addr> | delete <addr> | watch <addr> | unwatch <addr> | peek <addr> | poke <addr> <byte>)", chip8.program_counter());
+    if let Ok(opcode) = chip8.peek_opcode() {
+        let next = mnemonic(opcode).unwrap_or_else(|| format!("DW 0x{:04X}", opcode));
+        println!("Next instruction: {:04X}  {}", opcode, next);
+    }
+    loop {
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            return; // stdin closed, resume execution
+        }
+        match DebugCommand::parse(&line) {
+            Ok(DebugCommand::Continue) => return,
+            Ok(DebugCommand::Step(steps)) => {
+                chip8.break_after(steps);
+                return;
+            }
+            Ok(DebugCommand::RunTo(addr)) => {
+                chip8.run_to(addr);
+                return;
+            }
+            Ok(DebugCommand::Break(addr)) => chip8.add_breakpoint(addr),
+            Ok(DebugCommand::Delete(addr)) => chip8.remove_breakpoint(addr),
+            Ok(DebugCommand::Watch(addr)) => chip8.add_watchpoint(addr),
+            Ok(DebugCommand::Unwatch(addr)) => chip8.remove_watchpoint(addr),
+            Ok(DebugCommand::Peek(addr)) => match chip8.read_memory(addr) {
+                Ok(byte) => println!("{:#05x}: {:#04x}", addr, byte),
+                Err(error) => println!("{}", error),
+            },
+            Ok(DebugCommand::Poke(addr, byte)) => {
+                if let Err(error) = chip8.write_memory(addr, byte) {
+                    println!("{}", error);
+                }
+            }
+            Err(error) => println!("{}", error),
+        }
     }
 }
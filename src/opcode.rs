@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct OpCode {
     pub c: u8,
     pub x: u8,
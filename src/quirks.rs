@@ -0,0 +1,80 @@
+/// Behaviours that differ between CHIP-8 interpreters.
+/// Each flag is `true` when the interpreter should use the named behaviour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6/8XYE copy VY into VX before shifting (otherwise VX is shifted in place)
+    pub shift_uses_vy: bool,
+    /// BNNN is read as BXNN and jumps to XNN + VX (otherwise NNN + V0)
+    pub jump_uses_vx: bool,
+    /// FX55/FX65 leave I pointing past the last register they copied
+    pub memory_increments_index: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to 0
+    pub vf_reset: bool,
+    /// Sprites wrap around to the opposite edge of the screen (otherwise they are clipped)
+    pub wrap_sprites: bool,
+    /// DXYN waits for the next 60 Hz vertical blank, so at most one sprite is drawn per frame.
+    /// COSMAC VIP games rely on it to run at the right speed without flicker, and the
+    /// Timendus quirks test expects it in CHIP-8 mode.
+    pub display_wait: bool,
+    /// Enable the XO-CHIP extensions: F000 NNNN loads a 16-bit index, DXY0 draws a 16x16 sprite,
+    /// 5XY2/5XY3 save and load register ranges, skips jump over the whole F000 NNNN instruction
+    /// F002/FX3A set the audio pattern and pitch and FN01 selects the bitplanes
+    pub xo_chip: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::cosmac_vip()
+    }
+}
+
+impl Quirks {
+    /// Get a preset from its name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cosmac" => Some(Self::cosmac_vip()),
+            "superchip" => Some(Self::super_chip()),
+            "xochip" => Some(Self::xo_chip()),
+            _ => None,
+        }
+    }
+
+    /// The original COSMAC VIP interpreter
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            jump_uses_vx: false,
+            memory_increments_index: true,
+            vf_reset: true,
+            wrap_sprites: false,
+            display_wait: true,
+            xo_chip: false,
+        }
+    }
+
+    /// The SUPER-CHIP 1.1 interpreter for the HP48 calculators
+    pub fn super_chip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            jump_uses_vx: true,
+            memory_increments_index: false,
+            vf_reset: false,
+            wrap_sprites: false,
+            display_wait: false,
+            xo_chip: false,
+        }
+    }
+
+    /// The XO-CHIP extension of SUPER-CHIP, as implemented by Octo
+    pub fn xo_chip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            jump_uses_vx: false,
+            memory_increments_index: true,
+            vf_reset: false,
+            wrap_sprites: true,
+            display_wait: false,
+            xo_chip: true,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A key press or release, stamped with the number of instructions executed before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub cycle: u64,    // instructions executed since the recording started
+    pub key: u8,       // keypad key, from 0x0 to 0xF
+    pub pressed: bool, // true for a press, false for a release
+}
+
+/// The keypad input of a session, replayed to play it out again instruction for instruction.
+/// The seed of the random numbers is kept along with the input, as the game also depends on it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recorder {
+    seed: Option<u64>,       // seed of the CXNN random numbers during the recording
+    events: Vec<InputEvent>, // key changes, oldest first
+}
+
+impl Recorder {
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            seed,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    /// Log a change of `key` after `cycle` instructions
+    pub fn record(&mut self, cycle: u64, key: u8, pressed: bool) {
+        self.events.push(InputEvent {
+            cycle,
+            key,
+            pressed,
+        });
+    }
+
+    /// Load a recording saved by `save`
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid recording line: {}", line),
+            )
+        };
+        let mut recorder = Self::default();
+
+        for line in fs::read_to_string(path)?.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [] => {}
+                ["seed", seed] => recorder.seed = Some(seed.parse().map_err(|_| invalid(line))?),
+                [cycle, key, pressed] => {
+                    let cycle = cycle.parse().map_err(|_| invalid(line))?;
+                    let key = u8::from_str_radix(key, 16).map_err(|_| invalid(line))?;
+                    let pressed = match pressed {
+                        "1" => true,
+                        "0" => false,
+                        _ => return Err(invalid(line)),
+                    };
+                    recorder.record(cycle, key, pressed);
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+
+        Ok(recorder)
+    }
+
+    /// Save the recording to a file: the seed on the first line if there is one,
+    /// then one `cycle key pressed` line per event, e.g. `1520 A 1`
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut contents = String::new();
+        if let Some(seed) = self.seed {
+            contents.push_str(&format!("seed {}\n", seed));
+        }
+        for event in &self.events {
+            contents.push_str(&format!(
+                "{} {:X} {}\n",
+                event.cycle, event.key, event.pressed as u8
+            ));
+        }
+        fs::write(path, contents)
+    }
+}
@@ -0,0 +1,49 @@
+use crate::Framebuffer;
+
+/// A backend able to show the CHIP-8 screen.
+/// The main loop only talks to this trait, so SDL, terminal or headless backends are interchangeable.
+pub trait Renderer {
+    /// Present the content of the display buffer
+    fn draw(&mut self, buffer: &Framebuffer);
+
+    /// Present the content of the display buffer while the emulation is paused,
+    /// backends that cannot show it differently draw it as usual
+    fn draw_paused(&mut self, buffer: &Framebuffer) {
+        self.draw(buffer);
+    }
+
+    /// Show the measured frames and instructions per second over the display,
+    /// backends without an overlay ignore them
+    fn set_stats(&mut self, _fps: u32, _ips: u64) {}
+
+    /// React to the output being resized to `width` x `height`, backends with a fixed size ignore it
+    fn resize(&mut self, _width: u32, _height: u32) {}
+
+    /// Switch between windowed and fullscreen, backends without a window ignore it
+    fn toggle_fullscreen(&mut self) {}
+}
+
+/// The frame last painted by a backend that only repaints the pixels that changed
+#[derive(Debug, Default)]
+pub struct PreviousFrame {
+    frame: Option<Framebuffer>, // None when the next frame has to be painted whole
+}
+
+impl PreviousFrame {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Paint the next frame whole, e.g. when the screen was resized or painted over
+    pub fn forget(&mut self) {
+        self.frame = None;
+    }
+
+    /// Remember `buffer` as the frame being painted and get the frame it can be painted over,
+    /// None when it has to be painted whole, including after a change of resolution
+    pub fn replace(&mut self, buffer: &Framebuffer) -> Option<Framebuffer> {
+        self.frame.replace(*buffer).filter(|previous| {
+            (previous.width(), previous.height()) == (buffer.width(), buffer.height())
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use crate::Quirks;
+
+/// Name to pass to `--rom` to run the embedded ROM
+pub const BUILTIN_ROM_NAME: &str = "builtin";
+
+/// The IBM logo program, embedded so the emulator runs without any ROM file
+pub const BUILTIN_ROM: &[u8] = include_bytes!("../rom/IBM Logo.ch8");
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325; // 64-bit FNV-1a starting value
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3; // 64-bit FNV-1a multiplier
+
+/// A ROM whose quirks are known
+#[derive(Debug, Clone, Copy)]
+pub struct KnownRom {
+    pub fingerprint: u64,       // `rom_fingerprint` of the ROM file
+    pub title: &'static str,    // human readable name of the game
+    pub preset: &'static str,   // name of the quirks preset it needs
+    pub quirks: fn() -> Quirks, // constructor of the quirks preset
+}
+
+/// ROMs that do not run with the default COSMAC VIP quirks
+const KNOWN_ROMS: &[KnownRom] = &[KnownRom {
+    fingerprint: 0x8e54_7ebb_12c0_26b4,
+    title: "Space Invaders [David Winter]",
+    preset: "SUPER-CHIP",
+    quirks: Quirks::super_chip,
+}];
+
+/// A stable 64-bit hash (FNV-1a) identifying a ROM by its content
+pub fn rom_fingerprint(rom: &[u8]) -> u64 {
+    rom.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Look a ROM up in the table of known ROMs to get the quirks it needs
+pub fn identify_rom(rom: &[u8]) -> Option<&'static KnownRom> {
+    let fingerprint = rom_fingerprint(rom);
+    KNOWN_ROMS
+        .iter()
+        .find(|known| known.fingerprint == fingerprint)
+}
@@ -0,0 +1,21 @@
+use std::path::Path;
+
+use image::{GrayImage, ImageResult, Luma};
+
+use crate::{Framebuffer, DISPLAY_WIDTH};
+
+/// Save the display as a PNG image, lit pixels in white on a black background.
+/// Each CHIP-8 pixel becomes a `scale` x `scale` square at the standard resolution, so
+/// the image has the size of the window (pixels are halved in high resolution, as on screen).
+pub fn save_screenshot(buffer: &Framebuffer, scale: u32, path: &Path) -> ImageResult<()> {
+    let pixel_size = (DISPLAY_WIDTH as u32 * scale / buffer.width() as u32).max(1);
+    let image = GrayImage::from_fn(
+        buffer.width() as u32 * pixel_size,
+        buffer.height() as u32 * pixel_size,
+        |x, y| {
+            let lit = buffer.pixel((x / pixel_size) as usize, (y / pixel_size) as usize);
+            Luma([if lit { 255 } else { 0 }])
+        },
+    );
+    image.save(path)
+}
@@ -0,0 +1,35 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Number of opcode categories, one per value of the first nibble
+pub const OPCODE_CATEGORIES: usize = 16;
+
+/// Load an opcode histogram saved by `save_histogram`.
+/// A missing or corrupt file gives an empty histogram, so counting starts fresh.
+pub fn load_histogram(path: &Path) -> [u64; OPCODE_CATEGORIES] {
+    let mut histogram = [0; OPCODE_CATEGORIES];
+    let Ok(contents) = fs::read_to_string(path) else {
+        return histogram;
+    };
+
+    let counts: Vec<u64> = match contents.lines().map(|line| line.trim().parse()).collect() {
+        Ok(counts) => counts,
+        Err(_) => return histogram,
+    };
+    if counts.len() != OPCODE_CATEGORIES {
+        return histogram;
+    }
+
+    histogram.copy_from_slice(&counts);
+    histogram
+}
+
+/// Save an opcode histogram to a file, one count per line ordered by category
+pub fn save_histogram(path: &Path, histogram: &[u64; OPCODE_CATEGORIES]) -> io::Result<()> {
+    let contents: String = histogram
+        .iter()
+        .map(|count| format!("{}\n", count))
+        .collect();
+    fs::write(path, contents)
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::{Framebuffer, Renderer, NUM_KEYS};
+
+const CLEAR_SCREEN: &str = "\x1b[2J"; // ANSI escape erasing the whole terminal
+const CURSOR_HOME: &str = "\x1b[H"; // ANSI escape moving the cursor to the top-left corner
+
+/// Text-mode display drawing the screen with block characters, e.g. over SSH or in CI.
+/// Two pixel rows are packed in each line of text so the pixels stay roughly square.
+pub struct TerminalDisplay {
+    cleared: bool, // the terminal is cleared once, later frames are drawn over the previous one
+}
+
+impl Default for TerminalDisplay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerminalDisplay {
+    pub fn new() -> Self {
+        Self { cleared: false }
+    }
+}
+
+impl Renderer for TerminalDisplay {
+    fn draw(&mut self, buffer: &Framebuffer) {
+        let mut frame = String::new();
+        if !self.cleared {
+            frame.push_str(CLEAR_SCREEN);
+            self.cleared = true;
+        }
+        // Redraw in place instead of scrolling a new copy of the screen
+        frame.push_str(CURSOR_HOME);
+
+        for y in (0..buffer.height()).step_by(2) {
+            for x in 0..buffer.width() {
+                let top = buffer.pixel(x, y);
+                let bottom = y + 1 < buffer.height() && buffer.pixel(x, y + 1);
+                frame.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            frame.push('\n');
+        }
+
+        // A closed terminal must not stop the emulation, so write errors are ignored
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+/// How long a key stays pressed after the terminal sent it. Terminals only send characters,
+/// never key releases, so a key is released once its character stops repeating.
+/// It is longer than the delay before the keyboard auto-repeat starts on most systems.
+pub const KEY_HOLD: Duration = Duration::from_millis(600);
+
+/// Maps the characters typed in a terminal to the 16 keys of the CHIP-8 keypad,
+/// on the same 4x4 block of keys as the window's `Keymap`
+pub struct TerminalKeymap {
+    keys: HashMap<char, u8>,
+}
+
+impl TerminalKeymap {
+    /// Build a keymap from the characters of the 4x4 block of keys, listed row by row.
+    /// Each row may list several characters per key, e.g. digits and the unshifted AZERTY symbols.
+    fn from_rows(rows: [[&str; 4]; 4]) -> Self {
+        // CHIP-8 keypad layout, row by row
+        const KEYPAD: [[u8; 4]; 4] = [
+            [0x1, 0x2, 0x3, 0xC],
+            [0x4, 0x5, 0x6, 0xD],
+            [0x7, 0x8, 0x9, 0xE],
+            [0xA, 0x0, 0xB, 0xF],
+        ];
+
+        let mut keys = HashMap::new();
+        for (chars, &chip8_key) in rows.iter().flatten().zip(KEYPAD.iter().flatten()) {
+            for c in chars.chars() {
+                keys.insert(c, chip8_key);
+                keys.insert(c.to_ascii_uppercase(), chip8_key); // caps lock or shift held
+            }
+        }
+        Self { keys }
+    }
+
+    /// Keypad on the `1234`/`QWER`/`ASDF`/`ZXCV` block
+    pub fn qwerty() -> Self {
+        Self::from_rows([
+            ["1", "2", "3", "4"],
+            ["q", "w", "e", "r"],
+            ["a", "s", "d", "f"],
+            ["z", "x", "c", "v"],
+        ])
+    }
+
+    /// Keypad on the `&é"'`/`AZER`/`QSDF`/`WXCV` block, with or without shift on the top row
+    pub fn azerty() -> Self {
+        Self::from_rows([
+            ["1&", "2é", "3\"", "4'"],
+            ["a", "z", "e", "r"],
+            ["q", "s", "d", "f"],
+            ["w", "x", "c", "v"],
+        ])
+    }
+
+    /// Get a keymap by layout name (`qwerty` or `azerty`)
+    pub fn from_layout(layout: &str) -> Option<Self> {
+        match layout.to_lowercase().as_str() {
+            "qwerty" => Some(Self::qwerty()),
+            "azerty" => Some(Self::azerty()),
+            _ => None,
+        }
+    }
+
+    /// Get the CHIP-8 key for a typed character, if it is part of the keypad
+    pub fn map(&self, c: char) -> Option<u8> {
+        self.keys.get(&c).copied()
+    }
+}
+
+/// Keys held down on a terminal, released `KEY_HOLD` after their character was last received
+#[derive(Debug, Default)]
+pub struct HeldKeys {
+    last_pressed: [Option<Instant>; NUM_KEYS], // when each held key was last received
+}
+
+impl HeldKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the character of `key` was received at `now`.
+    /// Returns true if the key was not already held, i.e. it has just been pressed.
+    pub fn press(&mut self, key: u8, now: Instant) -> bool {
+        self.last_pressed[key as usize].replace(now).is_none()
+    }
+
+    /// Release the keys whose character was not received for `KEY_HOLD` at `now`, returning them
+    pub fn release_expired(&mut self, now: Instant) -> Vec<u8> {
+        let mut released = Vec::new();
+        for (key, last_pressed) in self.last_pressed.iter_mut().enumerate() {
+            if let Some(at) = *last_pressed {
+                if now.saturating_duration_since(at) >= KEY_HOLD {
+                    *last_pressed = None;
+                    released.push(key as u8);
+                }
+            }
+        }
+        released
+    }
+}
+
+/// Raw mode of the terminal: typed characters are read at once, without echo and without
+/// waiting for Enter. The previous mode is restored when dropped.
+#[cfg(unix)]
+pub struct TerminalInput {
+    original: libc::termios, // mode of the terminal before raw mode
+}
+
+#[cfg(unix)]
+impl TerminalInput {
+    /// Switch standard input to raw mode, failing when it is not a terminal, e.g. in CI
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: termios is plain data, filled in by tcgetattr before it is used
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: standard input stays open for the whole run and `original` is a valid pointer
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let input = Self { original };
+        input.enable_raw_mode()?;
+        Ok(input)
+    }
+
+    /// Read characters without echo, line buffering or signals (Ctrl+C is read as 0x03),
+    /// and without blocking when nothing was typed
+    pub fn enable_raw_mode(&self) -> io::Result<()> {
+        let mut raw = self.original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        set_terminal_mode(&raw)
+    }
+
+    /// Go back to the mode the terminal had before, e.g. to read a line at the debugger prompt
+    pub fn restore_mode(&self) -> io::Result<()> {
+        set_terminal_mode(&self.original)
+    }
+
+    /// Get the bytes typed since the last call, without waiting
+    pub fn read(&mut self) -> Vec<u8> {
+        let mut buffer = [0u8; 64];
+        // SAFETY: the buffer is valid for its whole length
+        let count =
+            unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), buffer.len()) };
+        buffer[..count.max(0) as usize].to_vec()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for TerminalInput {
+    fn drop(&mut self) {
+        // nothing more can be done if the terminal is gone
+        let _ = self.restore_mode();
+    }
+}
+
+/// Function to apply a terminal mode to standard input
+#[cfg(unix)]
+fn set_terminal_mode(mode: &libc::termios) -> io::Result<()> {
+    // SAFETY: `mode` is a valid termios obtained from tcgetattr
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, mode) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use std::time::{Duration, Instant};
+
+/// Precisely wait for the given duration.
+/// `std::thread::sleep` can overshoot by a whole scheduler tick, so the tail of
+/// the wait is spun to hit the deadline accurately.
+pub fn precise_wait(duration: Duration) {
+    spin_sleep::sleep(duration);
+}
+
+/// Caps how often the display is presented, independently of the CPU clock
+pub struct FrameLimiter {
+    frame_interval: Duration, // time between two presented frames
+    last_frame: Instant,      // when the last frame was presented
+}
+
+impl FrameLimiter {
+    pub fn new(fps: u32) -> Self {
+        let fps = fps.max(1); // guard against a division by zero
+        Self {
+            frame_interval: Duration::from_micros(1_000_000 / fps as u64),
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// Time left at `now` before the next frame is due
+    pub fn time_until_next_frame(&self, now: Instant) -> Duration {
+        self.frame_interval
+            .saturating_sub(now.saturating_duration_since(self.last_frame))
+    }
+
+    /// Returns true, and starts a new frame, if a frame is due at `now`
+    pub fn try_begin_frame(&mut self, now: Instant) -> bool {
+        if self.time_until_next_frame(now).is_zero() {
+            self.last_frame = now;
+            true
+        } else {
+            false
+        }
+    }
+}
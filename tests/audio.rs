@@ -0,0 +1,36 @@
+//! Validation of the audio settings given on the command line.
+#![cfg(feature = "sdl")]
+
+use chip_8::{
+    is_valid_audio_buffer_size, is_valid_beep_frequency, MAX_AUDIO_BUFFER, MIN_AUDIO_BUFFER,
+    SAMPLE_RATE,
+};
+
+#[test]
+fn audio_buffer_size_is_a_power_of_two() {
+    for samples in [256, 512, 1024, 4096] {
+        assert!(is_valid_audio_buffer_size(samples), "{}", samples);
+    }
+    for samples in [0, 300, 500, 1000, 1025] {
+        assert!(!is_valid_audio_buffer_size(samples), "{}", samples);
+    }
+}
+
+#[test]
+fn audio_buffer_size_is_within_bounds() {
+    assert!(is_valid_audio_buffer_size(MIN_AUDIO_BUFFER));
+    assert!(is_valid_audio_buffer_size(MAX_AUDIO_BUFFER));
+    assert!(!is_valid_audio_buffer_size(MIN_AUDIO_BUFFER / 2));
+    assert!(!is_valid_audio_buffer_size(MAX_AUDIO_BUFFER * 2));
+}
+
+#[test]
+fn beep_frequency_is_below_the_nyquist_limit() {
+    for frequency in [20.0, 440.0, 22049.0] {
+        assert!(is_valid_beep_frequency(frequency), "{}", frequency);
+    }
+    let nyquist = SAMPLE_RATE as f32 / 2.0;
+    for frequency in [0.0, -440.0, nyquist, 100_000.0, f32::NAN, f32::INFINITY] {
+        assert!(!is_valid_beep_frequency(frequency), "{}", frequency);
+    }
+}
@@ -0,0 +1,24 @@
+//! The ROM embedded in the binary, used when no ROM file is available.
+
+use chip_8::{Chip8, Quirks, BUILTIN_ROM, PROGRAM_START};
+
+#[test]
+fn builtin_rom_is_loaded_at_0x200() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom_slice(BUILTIN_ROM).unwrap();
+
+    assert!(!BUILTIN_ROM.is_empty());
+    assert_eq!(PROGRAM_START, 0x200);
+    let loaded = &chip8.memory()[PROGRAM_START..PROGRAM_START + BUILTIN_ROM.len()];
+    assert_eq!(loaded, BUILTIN_ROM);
+    assert!(loaded.iter().any(|&byte| byte != 0));
+}
+
+#[test]
+fn builtin_rom_runs() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom_slice(BUILTIN_ROM).unwrap();
+
+    assert_eq!(chip8.run_until(1000, Chip8::is_halted), Ok(true));
+    assert!(chip8.framebuffer().iter().any(|&pixel| pixel != 0));
+}
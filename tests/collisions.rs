@@ -0,0 +1,156 @@
+//! VF collision behaviour of DXYN at the screen edges, where sprites are clipped or wrapped.
+
+use chip_8::{Chip8, Quirks};
+
+const SPRITES: u16 = 0x300; // where the test sprites are stored, well after the program
+
+/// The four instructions drawing the `rows` rows of the sprite at `sprite` at (`x`, `y`)
+fn draw(x: u8, y: u8, sprite: u16, rows: u8) -> [u8; 8] {
+    [
+        0x60,
+        x, // V0 = x
+        0x61,
+        y, // V1 = y
+        0xA0 | (sprite >> 8) as u8,
+        sprite as u8, // I = sprite
+        0xD0,
+        0x10 | rows, // draw at (V0, V1)
+    ]
+}
+
+/// Load the sprites and a program made of draws, ready to run one draw at a time
+fn setup(quirks: Quirks, sprites: &[u8], draws: &[[u8; 8]]) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks);
+    chip8.load_rom_slice(&draws.concat()).unwrap();
+    for (offset, &byte) in sprites.iter().enumerate() {
+        chip8.write_memory(SPRITES + offset as u16, byte).unwrap();
+    }
+    chip8
+}
+
+/// Execute the next draw, returning VF and the number of collided pixels
+fn next_draw(chip8: &mut Chip8) -> (u8, u32) {
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+    (chip8.register(0xF), chip8.last_collision_count())
+}
+
+fn clipping() -> Quirks {
+    Quirks {
+        wrap_sprites: false,
+        ..Quirks::cosmac_vip()
+    }
+}
+
+fn wrapping() -> Quirks {
+    Quirks {
+        wrap_sprites: true,
+        ..Quirks::cosmac_vip()
+    }
+}
+
+#[test]
+fn clipped_sprite_collides_on_visible_pixels_only() {
+    // 0xFF at x = 60 only shows its first 4 pixels
+    let mut chip8 = setup(
+        clipping(),
+        &[0xFF],
+        &[draw(60, 0, SPRITES, 1), draw(60, 0, SPRITES, 1)],
+    );
+
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+    assert_eq!(next_draw(&mut chip8), (1, 4));
+}
+
+#[test]
+fn clipped_pixels_do_not_collide_on_the_opposite_edge() {
+    // The 4 pixels cut off at the right edge must not appear at x = 0
+    let mut chip8 = setup(
+        clipping(),
+        &[0xFF, 0xF0],
+        &[draw(60, 0, SPRITES, 1), draw(0, 0, SPRITES + 1, 1)],
+    );
+
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+}
+
+#[test]
+fn clipped_rows_do_not_collide() {
+    // Only the first of the two rows fits at y = 31
+    let mut chip8 = setup(
+        clipping(),
+        &[0xFF, 0xFF],
+        &[
+            draw(0, 31, SPRITES, 2),
+            draw(0, 31, SPRITES, 2),
+            draw(0, 0, SPRITES, 1),
+        ],
+    );
+
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+    assert_eq!(next_draw(&mut chip8), (1, 8));
+    // the second row was not wrapped to the top either
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+}
+
+#[test]
+fn wrapped_pixels_collide_on_the_opposite_edge() {
+    // 0xFF at x = 60 wraps its last 4 pixels to x = 0..3
+    let mut chip8 = setup(
+        wrapping(),
+        &[0xFF, 0x0F, 0xF0],
+        &[
+            draw(60, 0, SPRITES, 1),
+            draw(0, 0, SPRITES + 1, 1),
+            draw(0, 0, SPRITES + 2, 1),
+        ],
+    );
+
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+    // x = 4..7 is still blank
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+    // x = 0..3 holds the wrapped pixels
+    assert_eq!(next_draw(&mut chip8), (1, 4));
+}
+
+#[test]
+fn wrapped_rows_collide_at_the_top() {
+    let mut chip8 = setup(
+        wrapping(),
+        &[0x00, 0x81],
+        &[draw(0, 31, SPRITES, 2), draw(0, 0, SPRITES + 1, 1)],
+    );
+
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+    // the second row was wrapped to y = 0
+    assert_eq!(next_draw(&mut chip8), (1, 2));
+}
+
+#[test]
+fn starting_coordinates_wrap_in_both_modes() {
+    // x = 64 + 2 starts the sprite at x = 2, even when the sprite body is clipped
+    for quirks in [clipping(), wrapping()] {
+        let mut chip8 = setup(
+            quirks,
+            &[0x80],
+            &[draw(66, 32, SPRITES, 1), draw(2, 0, SPRITES, 1)],
+        );
+
+        assert_eq!(next_draw(&mut chip8), (0, 0));
+        assert_eq!(next_draw(&mut chip8), (1, 1));
+    }
+}
+
+#[test]
+fn vf_is_one_whatever_the_number_of_collisions() {
+    let mut chip8 = setup(
+        clipping(),
+        &[0xFF, 0xFF, 0xFF],
+        &[draw(8, 8, SPRITES, 3), draw(8, 8, SPRITES, 3)],
+    );
+
+    assert_eq!(next_draw(&mut chip8), (0, 0));
+    assert_eq!(next_draw(&mut chip8), (1, 24));
+}
@@ -0,0 +1,195 @@
+//! The debugger: parsing prompt commands, peeking and poking memory, breakpoints and the in-code break opcode.
+
+use chip_8::{Chip8, DebugCommand, Quirks};
+
+#[test]
+fn parses_peek_and_poke() {
+    assert_eq!(
+        DebugCommand::parse("peek 0x300"),
+        Ok(DebugCommand::Peek(0x300))
+    );
+    assert_eq!(
+        DebugCommand::parse("poke 300 0xAB"),
+        Ok(DebugCommand::Poke(0x300, 0xAB))
+    );
+    assert!(DebugCommand::parse("peek").is_err());
+    assert!(DebugCommand::parse("poke 0x300").is_err());
+    assert!(DebugCommand::parse("poke 0x300 0x100").is_err()); // not a byte
+}
+
+#[test]
+fn poked_byte_is_peeked_back() {
+    let mut chip8 = Chip8::new(Quirks::default());
+
+    chip8.write_memory(0x300, 0xAB).unwrap();
+    assert_eq!(chip8.read_memory(0x300), Ok(0xAB));
+
+    // writes outside of memory are refused
+    let end = chip8.memory_size() as u16;
+    assert!(chip8.write_memory(end, 0xAB).is_err());
+    assert!(chip8.read_memory(end).is_err());
+}
+
+/// Execute the configured break opcode 0xF0FF, which would otherwise be unknown
+fn run_break_opcode(debug: bool) -> Chip8 {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.set_debug(debug);
+    chip8.set_break_opcode(Some(0xF0FF));
+    chip8.load_rom_slice(&[0xF0, 0xFF]).unwrap();
+    chip8.step().unwrap();
+    chip8
+}
+
+#[test]
+fn break_opcode_breaks_in_debug_mode() {
+    let mut chip8 = run_break_opcode(true);
+
+    assert!(chip8.take_break_request());
+    assert_eq!(chip8.program_counter(), 0x202);
+}
+
+#[test]
+fn break_opcode_is_a_no_op_without_debug_mode() {
+    let mut chip8 = run_break_opcode(false);
+
+    assert!(!chip8.take_break_request());
+    assert_eq!(chip8.program_counter(), 0x202);
+    assert_eq!(chip8.registers(), &[0; 16]);
+}
+
+#[test]
+fn parses_navigation_commands() {
+    assert_eq!(DebugCommand::parse(""), Ok(DebugCommand::Continue));
+    assert_eq!(DebugCommand::parse("c"), Ok(DebugCommand::Continue));
+    assert_eq!(DebugCommand::parse("step"), Ok(DebugCommand::Step(1)));
+    assert_eq!(DebugCommand::parse("s 5"), Ok(DebugCommand::Step(5)));
+    assert_eq!(DebugCommand::parse("step 0"), Ok(DebugCommand::Step(0)));
+    assert_eq!(
+        DebugCommand::parse("run-to 0x2A0"),
+        Ok(DebugCommand::RunTo(0x2A0))
+    );
+
+    assert!(DebugCommand::parse("step -1").is_err());
+    assert!(DebugCommand::parse("run-to").is_err());
+    assert!(DebugCommand::parse("run-to 0x2A0 0x2A2").is_err());
+    assert!(DebugCommand::parse("jump 0x2A0").is_err());
+}
+
+// Count up in V0 forever: 0x200 adds, 0x202 jumps back
+const COUNTER: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+/// Step until a break is requested, returning the number of instructions executed
+fn steps_until_break(chip8: &mut Chip8) -> u64 {
+    let start = chip8.instruction_count();
+    while !chip8.take_break_request() {
+        assert!(chip8.instruction_count() - start < 100, "no break");
+        chip8.step().unwrap();
+    }
+    chip8.instruction_count() - start
+}
+
+fn counter() -> Chip8 {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom_slice(&COUNTER).unwrap();
+    chip8
+}
+
+#[test]
+fn step_breaks_after_n_instructions() {
+    let mut chip8 = counter();
+    chip8.break_after(3);
+    assert_eq!(steps_until_break(&mut chip8), 3);
+
+    // zero steps breaks without executing anything
+    chip8.break_after(0);
+    assert_eq!(steps_until_break(&mut chip8), 0);
+}
+
+#[test]
+fn run_to_stops_at_the_target_once() {
+    let mut chip8 = counter();
+    chip8.run_to(0x202);
+    assert_eq!(steps_until_break(&mut chip8), 1);
+    assert_eq!(chip8.program_counter(), 0x202);
+
+    // the target is forgotten once reached
+    for _ in 0..10 {
+        chip8.step().unwrap();
+    }
+    assert!(!chip8.take_break_request());
+}
+
+#[test]
+fn run_to_the_current_address_waits_for_the_program_to_come_back() {
+    let mut chip8 = counter();
+    chip8.run_to(0x200);
+    assert_eq!(steps_until_break(&mut chip8), 2);
+    assert_eq!(chip8.program_counter(), 0x200);
+    assert_eq!(chip8.register(0), 1);
+}
+
+#[test]
+fn breakpoint_at_the_program_start_breaks_before_running_it() {
+    let mut chip8 = counter();
+    chip8.add_breakpoint(0x200);
+
+    assert_eq!(chip8.step(), Ok(None));
+    assert!(chip8.take_break_request());
+    assert_eq!(chip8.program_counter(), 0x200);
+    assert_eq!(chip8.instruction_count(), 0);
+    assert_eq!(chip8.register(0), 0);
+
+    // resuming runs the instruction at the breakpoint instead of breaking again
+    assert!(chip8.step().unwrap().is_some());
+    assert_eq!(chip8.register(0), 1);
+    assert!(!chip8.take_break_request());
+
+    // coming back to it breaks once, before running it again
+    assert_eq!(steps_until_break(&mut chip8), 1);
+    assert_eq!(chip8.program_counter(), 0x200);
+    assert_eq!(chip8.register(0), 1);
+    chip8.step().unwrap();
+    assert_eq!(chip8.register(0), 2);
+}
+
+#[test]
+fn cycle_draws_nothing_when_stopping_at_a_breakpoint() {
+    // draw the font sprite at I = 0 at (V0, V0)
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom_slice(&[0xD0, 0x05]).unwrap();
+    chip8.add_breakpoint(0x200);
+
+    assert_eq!(chip8.cycle(), Ok(false));
+    assert!(chip8.take_break_request());
+    assert_eq!(chip8.cycle(), Ok(true));
+}
+
+#[test]
+fn break_opcode_counts_as_an_instruction() {
+    // break opcode at 0x200, then count up in V0
+    let program = [0xF0, 0xFF, 0x70, 0x01, 0x12, 0x02];
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.set_break_opcode(Some(0xF0FF));
+    chip8.load_rom_slice(&program).unwrap();
+
+    chip8.break_after(2);
+    assert_eq!(steps_until_break(&mut chip8), 2);
+    assert_eq!(chip8.register(0), 1);
+
+    // run-to an address reached by the break opcode itself
+    chip8.reset(true);
+    chip8.run_to(0x202);
+    assert_eq!(steps_until_break(&mut chip8), 1);
+}
+
+#[test]
+fn break_opcode_keeps_a_watchpoint_break_outside_debug_mode() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.set_break_opcode(Some(0xF0FF));
+    chip8.add_watchpoint(0x200);
+    chip8.load_rom_slice(&[0xF0, 0xFF]).unwrap();
+    chip8.step().unwrap();
+
+    assert!(chip8.take_break_request());
+    assert_eq!(chip8.take_watch_hits().len(), 1);
+}
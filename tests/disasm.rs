@@ -0,0 +1,26 @@
+//! Listing ROMs as mnemonics.
+
+use chip_8::{disassemble, PROGRAM_START};
+
+const ROM: [u8; 5] = [0x00, 0xE0, 0x12, 0x00, 0xAB];
+
+#[test]
+fn listing_starts_at_the_program_start() {
+    assert_eq!(
+        disassemble(&ROM, PROGRAM_START as u16),
+        [
+            (0x200, "CLS".to_string()),
+            (0x202, "JP 0x200".to_string()),
+            (0x204, "DB 0xAB".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn listing_follows_a_custom_program_start() {
+    let addresses: Vec<u16> = disassemble(&ROM, 0x600)
+        .into_iter()
+        .map(|(addr, _)| addr)
+        .collect();
+    assert_eq!(addresses, [0x600, 0x602, 0x604]);
+}
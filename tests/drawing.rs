@@ -0,0 +1,150 @@
+//! Sprite drawing with DXYN and direct access to the display buffer.
+
+use chip_8::{Chip8, DrawMode, DrawTrace, Framebuffer, Quirks};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Draw the 0 font character at (0, 0) twice
+const DRAW_TWICE: [u8; 8] = [
+    0xA0, 0x50, // I = 0x050, the 0 character
+    0x60, 0x00, // V0 = 0
+    0xD0, 0x05, // draw 5 rows at (V0, V0)
+    0xD0, 0x05, // draw them again
+];
+
+/// Run the four instructions of `DRAW_TWICE` in `draw_mode`
+fn draw_twice(draw_mode: DrawMode) -> Chip8 {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.set_draw_mode(draw_mode);
+    chip8.load_rom_slice(&DRAW_TWICE).unwrap();
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+    chip8
+}
+
+#[test]
+fn xor_mode_erases_a_sprite_drawn_twice() {
+    let chip8 = draw_twice(DrawMode::Xor);
+
+    assert!(chip8.framebuffer().iter().all(|&pixel| pixel == 0));
+    assert_eq!(chip8.register(0xF), 1);
+}
+
+#[test]
+fn overwrite_mode_keeps_a_sprite_drawn_twice() {
+    let chip8 = draw_twice(DrawMode::Overwrite);
+
+    // top row of the 0 character, 0xF0
+    assert!((0..4).all(|x| chip8.display_buffer().pixel(x, 0)));
+    assert!(!chip8.display_buffer().pixel(4, 0));
+    // the collision is still reported
+    assert_eq!(chip8.register(0xF), 1);
+}
+
+#[test]
+fn draw_trace_reports_the_pixels_set_and_cleared() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    let draws = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&draws);
+    chip8.set_draw_trace_hook(Box::new(move |draw| log.borrow_mut().push(draw.clone())));
+    chip8.load_rom_slice(&DRAW_TWICE).unwrap();
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+
+    let sprite = vec![0xF0, 0x90, 0x90, 0x90, 0xF0];
+    assert_eq!(
+        *draws.borrow(),
+        [
+            DrawTrace {
+                sprite: sprite.clone(),
+                x: 0,
+                y: 0,
+                pixels_set: 14,
+                pixels_cleared: 0,
+                collision: false,
+            },
+            DrawTrace {
+                sprite,
+                x: 0,
+                y: 0,
+                pixels_set: 0,
+                pixels_cleared: 14,
+                collision: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn installed_display_buffer_is_cleared_by_00e0() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    let mut pattern = Framebuffer::new();
+    for x in (0..pattern.width()).step_by(2) {
+        pattern.set_pixel(x, x % pattern.height(), true);
+    }
+    chip8.set_display_buffer(pattern);
+    assert_eq!(*chip8.display_buffer(), pattern);
+    assert!(chip8.take_display_dirty());
+
+    chip8.load_rom_slice(&[0x00, 0xE0]).unwrap();
+    chip8.step().unwrap();
+
+    assert_eq!(*chip8.display_buffer(), Framebuffer::new());
+}
+
+#[test]
+fn pixel_x_is_the_column_and_y_the_row() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    let mut buffer = Framebuffer::new();
+    buffer.set_pixel(5, 2, true);
+    buffer.set_pixel(63, 31, true); // bottom-right corner
+    chip8.set_display_buffer(buffer);
+
+    assert!(buffer.pixel(5, 2));
+    assert!(!buffer.pixel(2, 5));
+    // the row-major export puts pixel (x, y) at y * width + x
+    let pixels = chip8.framebuffer();
+    let lit: Vec<usize> = (0..pixels.len()).filter(|&i| pixels[i] != 0).collect();
+    assert_eq!(lit, [2 * 64 + 5, 31 * 64 + 63]);
+}
+
+/// Draw the 16x16 sprite of 32 0xFF bytes at 0x300 with DXY0, after `setup` instructions
+fn draw_large_sprite(quirks: Quirks, setup: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks);
+    let program = [setup, &[0xA3, 0x00, 0xD0, 0x00]].concat(); // I = 0x300, draw at (V0, V0)
+    chip8.load_rom_slice(&program).unwrap();
+    chip8.load_at(0x300, &[0xFF; 32]).unwrap();
+    for _ in 0..program.len() / 2 {
+        chip8.step().unwrap();
+    }
+    chip8
+}
+
+#[test]
+fn super_chip_draws_16x16_sprites_in_high_resolution() {
+    let chip8 = draw_large_sprite(Quirks::super_chip(), &[0x00, 0xFF]);
+
+    assert_eq!(chip8.dimensions(), (128, 64));
+    let buffer = chip8.display_buffer();
+    let lit = (0..64)
+        .flat_map(|y| (0..128).map(move |x| (x, y)))
+        .filter(|&(x, y)| buffer.pixel(x, y));
+    assert!(lit.clone().all(|(x, y)| x < 16 && y < 16));
+    assert_eq!(lit.count(), 256);
+}
+
+#[test]
+fn xo_chip_draws_16x16_sprites_in_low_resolution() {
+    let chip8 = draw_large_sprite(Quirks::xo_chip(), &[]);
+
+    assert_eq!(
+        chip8
+            .framebuffer()
+            .iter()
+            .filter(|&&pixel| pixel != 0)
+            .count(),
+        256
+    );
+}
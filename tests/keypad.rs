@@ -0,0 +1,52 @@
+//! Waiting for a key with FX0A, as seen from outside the CPU.
+
+use chip_8::{Chip8, Quirks, MAX_MEMORY_SIZE};
+
+// V3 = key, then V4 = 1
+const GET_KEY: [u8; 4] = [0xF3, 0x0A, 0x64, 0x01];
+
+#[test]
+fn waiting_lasts_until_the_key_is_released() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom_slice(&GET_KEY).unwrap();
+    assert!(!chip8.is_waiting_for_key());
+
+    chip8.step().unwrap();
+    assert!(chip8.is_waiting_for_key());
+
+    chip8.set_key(0xB, true);
+    chip8.step().unwrap();
+    assert!(chip8.is_waiting_for_key()); // still held
+
+    chip8.set_key(0xB, false);
+    chip8.step().unwrap();
+    assert!(!chip8.is_waiting_for_key());
+    assert_eq!(chip8.register(3), 0xB);
+
+    chip8.step().unwrap();
+    assert_eq!(chip8.register(4), 1);
+}
+
+#[test]
+fn waiting_at_the_end_of_memory_stays_on_the_instruction() {
+    // FX0A in the last two bytes of a 64 KB memory, the program counter wraps to 0 when fetching it
+    let mut chip8 = Chip8::builder()
+        .memory_size(MAX_MEMORY_SIZE)
+        .program_start(0xFFFE)
+        .build();
+    chip8.load_rom_slice(&GET_KEY[..2]).unwrap();
+
+    chip8.step().unwrap();
+    assert!(chip8.is_waiting_for_key());
+    assert_eq!(chip8.program_counter(), 0xFFFE);
+
+    chip8.set_key(0x7, true);
+    chip8.step().unwrap();
+    assert_eq!(chip8.program_counter(), 0xFFFE); // still held
+
+    chip8.set_key(0x7, false);
+    chip8.step().unwrap();
+    assert!(!chip8.is_waiting_for_key());
+    assert_eq!(chip8.register(3), 0x7);
+    assert_eq!(chip8.program_counter(), 0x0000);
+}
@@ -0,0 +1,105 @@
+//! Memory accesses near the end of memory: DXYN and FX33/FX55/FX65 raise an error instead
+//! of panicking, and `load_at` refuses data that does not fit.
+
+use chip_8::{Chip8, Chip8Error, LoadError, Quirks};
+
+/// Run `opcode` with I set to `index`
+fn run_with_index(index: u16, opcode: u16) -> Result<(), Chip8Error> {
+    let mut chip8 = Chip8::new(Quirks::default());
+    let program = [
+        0xA0 | (index >> 8) as u8,
+        index as u8,
+        (opcode >> 8) as u8,
+        opcode as u8,
+    ];
+    chip8.load_rom_slice(&program).unwrap();
+    chip8.step()?;
+    chip8.step()?;
+    Ok(())
+}
+
+#[test]
+fn bcd_must_fit_in_memory() {
+    assert_eq!(run_with_index(0xFFD, 0xF033), Ok(()));
+    assert_eq!(
+        run_with_index(0xFFE, 0xF033),
+        Err(Chip8Error::MemoryOutOfBounds(0xFFE))
+    );
+}
+
+#[test]
+fn register_store_must_fit_in_memory() {
+    assert_eq!(run_with_index(0xFFC, 0xF355), Ok(()));
+    assert_eq!(
+        run_with_index(0xFFD, 0xF355),
+        Err(Chip8Error::MemoryOutOfBounds(0xFFD))
+    );
+}
+
+#[test]
+fn register_load_must_fit_in_memory() {
+    assert_eq!(run_with_index(0xFFF, 0xF065), Ok(()));
+    assert_eq!(
+        run_with_index(0xFFF, 0xF165),
+        Err(Chip8Error::MemoryOutOfBounds(0xFFF))
+    );
+}
+
+#[test]
+fn sprite_must_fit_in_memory() {
+    assert_eq!(run_with_index(0xFFE, 0xD002), Ok(()));
+    assert_eq!(
+        run_with_index(0xFFF, 0xD002),
+        Err(Chip8Error::MemoryOutOfBounds(0xFFF))
+    );
+}
+
+#[test]
+fn sprites_of_both_planes_must_fit_in_memory() {
+    // XO-CHIP: with both planes selected, DXY2 reads two sprites of two bytes
+    let draw = |index: u16| {
+        let mut chip8 = Chip8::new(Quirks::xo_chip());
+        let program = [
+            0xF3,
+            0x01,
+            0xA0 | (index >> 8) as u8,
+            index as u8,
+            0xD0,
+            0x02,
+        ];
+        chip8.load_rom_slice(&program).unwrap();
+        (0..3).try_for_each(|_| chip8.step().map(|_| ()))
+    };
+    assert_eq!(draw(0xFFC), Ok(()));
+    assert_eq!(draw(0xFFD), Err(Chip8Error::MemoryOutOfBounds(0xFFD)));
+}
+
+#[test]
+fn data_can_be_loaded_anywhere_in_memory() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_at(0xFFC, &[1, 2, 3, 4]).unwrap();
+    chip8.load_at(0x50, &[0xAA]).unwrap();
+
+    assert_eq!(&chip8.memory()[0xFFC..], &[1, 2, 3, 4]);
+    assert_eq!(chip8.memory()[0x50], 0xAA);
+}
+
+#[test]
+fn data_must_fit_in_memory() {
+    let mut chip8 = Chip8::new(Quirks::default());
+
+    assert_eq!(
+        chip8.load_at(0xFFD, &[1, 2, 3, 4]),
+        Err(LoadError::RomTooLarge {
+            size: 4,
+            available: 3
+        })
+    );
+    assert_eq!(
+        chip8.load_at(0x2000, &[1]),
+        Err(LoadError::RomTooLarge {
+            size: 1,
+            available: 0
+        })
+    );
+}
@@ -0,0 +1,153 @@
+//! Instructions whose behaviour depends on the quirks or on the registers they name.
+
+use chip_8::{Chip8, Chip8Error, Quirks};
+
+/// Load `program` and execute its first `steps` instructions
+fn run(quirks: Quirks, program: &[u8], steps: usize) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks);
+    chip8.load_rom_slice(program).unwrap();
+    run_loaded(&mut chip8, steps);
+    chip8
+}
+
+/// Execute the next `steps` instructions of the loaded program
+fn run_loaded(chip8: &mut Chip8, steps: usize) {
+    for _ in 0..steps {
+        chip8.step().unwrap();
+    }
+}
+
+// V0 = 0x10, V3 = 0x20, then B340
+const JUMP_WITH_OFFSET: [u8; 6] = [0x60, 0x10, 0x63, 0x20, 0xB3, 0x40];
+
+#[test]
+fn jump_with_offset_adds_v0() {
+    let chip8 = run(Quirks::cosmac_vip(), &JUMP_WITH_OFFSET, 3);
+    assert_eq!(chip8.program_counter(), 0x340 + 0x10);
+}
+
+#[test]
+fn jump_with_offset_adds_vx_with_the_quirk() {
+    let chip8 = run(Quirks::super_chip(), &JUMP_WITH_OFFSET, 3);
+    assert_eq!(chip8.program_counter(), 0x340 + 0x20); // read as B3NN, offset by V3
+}
+
+#[test]
+fn plane_selection_is_xo_chip_only() {
+    // F201 selects the second plane, then the 0 character is drawn on it
+    let program = [0xF2, 0x01, 0x60, 0x00, 0xA0, 0x50, 0xD0, 0x05];
+    let chip8 = run(Quirks::xo_chip(), &program, 4);
+    assert_eq!(chip8.display_buffer().planes(0, 0), 0b10);
+
+    for quirks in [Quirks::cosmac_vip(), Quirks::super_chip()] {
+        let mut chip8 = Chip8::new(quirks);
+        chip8.load_rom_slice(&program).unwrap();
+        assert_eq!(chip8.step(), Err(Chip8Error::UnknownOpcode(0xF201)));
+    }
+}
+
+/// Execute the 8XYN `opcode` with VF and V1 set beforehand, returning VF
+fn vf_after(vf: u8, v1: u8, opcode: u16) -> u8 {
+    let program = [0x6F, vf, 0x61, v1, (opcode >> 8) as u8, opcode as u8];
+    run(Quirks::cosmac_vip(), &program, 3).register(0xF)
+}
+
+#[test]
+fn subtraction_into_vf_keeps_the_borrow_flag() {
+    // 8F15: VF = VF - V1
+    assert_eq!(vf_after(5, 3, 0x8F15), 1); // no borrow, the difference 2 is overwritten
+    assert_eq!(vf_after(3, 5, 0x8F15), 0); // borrow
+    assert_eq!(vf_after(3, 3, 0x8F15), 1);
+
+    // 8F17: VF = V1 - VF
+    assert_eq!(vf_after(3, 5, 0x8F17), 1);
+    assert_eq!(vf_after(5, 3, 0x8F17), 0);
+    assert_eq!(vf_after(3, 3, 0x8F17), 1);
+}
+
+#[test]
+fn arithmetic_into_vf_ends_with_the_flag() {
+    // (opcode, VF, V1, expected VF): the result is always overwritten by the flag
+    let cases = [
+        (0x8F14, 0xF0, 0x20, 1), // carry
+        (0x8F14, 0x10, 0x20, 0), // no carry, the sum 0x30 is lost
+        (0x8F15, 0x30, 0x20, 1),
+        (0x8F15, 0x20, 0x30, 0),
+        (0x8F16, 0x00, 0x03, 1), // shifts V1 with the COSMAC VIP quirk, bit 0 out
+        (0x8F16, 0xFF, 0x02, 0),
+        (0x8F17, 0x20, 0x30, 1),
+        (0x8F17, 0x30, 0x20, 0),
+        (0x8F1E, 0x00, 0x80, 1), // bit 7 out
+        (0x8F1E, 0xFF, 0x40, 0),
+    ];
+    for (opcode, vf, v1, expected) in cases {
+        assert_eq!(vf_after(vf, v1, opcode), expected, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn shifting_vf_in_place_ends_with_the_flag() {
+    // SUPER-CHIP shifts VX itself, here VF = 0x81
+    let shift = |opcode: u16| {
+        let program = [0x6F, 0x81, (opcode >> 8) as u8, opcode as u8];
+        run(Quirks::super_chip(), &program, 2).register(0xF)
+    };
+    assert_eq!(shift(0x8F06), 1); // 0x40 is lost
+    assert_eq!(shift(0x8F0E), 1); // 0x02 is lost
+}
+
+/// Set V1, V2, V3 to 1, 2, 3 and I to 0x300, then run the 5XYN `opcode` in XO-CHIP mode
+fn register_range(opcode: u16) -> Chip8 {
+    let program = [
+        0x61,
+        1,
+        0x62,
+        2,
+        0x63,
+        3,
+        0xA3,
+        0x00,
+        (opcode >> 8) as u8,
+        opcode as u8,
+    ];
+    let mut chip8 = Chip8::new(Quirks::xo_chip());
+    chip8.load_rom_slice(&program).unwrap();
+    chip8.load_at(0x300, &[0xA, 0xB, 0xC, 0xD]).unwrap();
+    run_loaded(&mut chip8, 5);
+    chip8
+}
+
+#[test]
+fn register_range_save() {
+    assert_eq!(
+        &register_range(0x5132).memory()[0x300..0x304],
+        [1, 2, 3, 0xD]
+    ); // X < Y
+    assert_eq!(
+        &register_range(0x5312).memory()[0x300..0x304],
+        [3, 2, 1, 0xD]
+    ); // X > Y, reversed
+    assert_eq!(
+        &register_range(0x5222).memory()[0x300..0x304],
+        [2, 0xB, 0xC, 0xD]
+    ); // X == Y
+}
+
+#[test]
+fn register_range_load() {
+    assert_eq!(
+        &register_range(0x5133).registers()[..5],
+        [0, 0xA, 0xB, 0xC, 0]
+    ); // X < Y
+    assert_eq!(
+        &register_range(0x5313).registers()[..5],
+        [0, 0xC, 0xB, 0xA, 0]
+    ); // X > Y, reversed
+    assert_eq!(&register_range(0x5223).registers()[..5], [0, 1, 0xA, 3, 0]); // X == Y
+}
+
+#[test]
+fn register_range_leaves_the_index_alone() {
+    assert_eq!(register_range(0x5132).index_register(), 0x300);
+    assert_eq!(register_range(0x5133).index_register(), 0x300);
+}
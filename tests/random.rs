@@ -0,0 +1,52 @@
+//! Reproducibility of the CXNN random numbers when a seed is given.
+
+use chip_8::{Chip8, Quirks};
+
+// V0..V7 = random bytes, then loop forever
+const PROGRAM: [u8; 18] = [
+    0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF, 0xC3, 0xFF, 0xC4, 0xFF, 0xC5, 0xFF, 0xC6, 0xFF, 0xC7, 0xFF,
+    0x12, 0x10,
+];
+
+/// The random bytes drawn by the program
+fn random_bytes(mut chip8: Chip8) -> Vec<u8> {
+    chip8.load_rom_slice(&PROGRAM).unwrap();
+    for _ in 0..8 {
+        chip8.step().unwrap();
+    }
+    (0..8).map(|x| chip8.register(x)).collect()
+}
+
+#[test]
+fn same_seed_gives_the_same_numbers() {
+    let first = random_bytes(Chip8::new_with_seed(Quirks::default(), 42));
+    let second = random_bytes(Chip8::builder().seed(42).build());
+
+    assert_eq!(first, second);
+}
+
+#[test]
+fn different_seeds_give_different_numbers() {
+    let first = random_bytes(Chip8::new_with_seed(Quirks::default(), 1));
+    let second = random_bytes(Chip8::new_with_seed(Quirks::default(), 2));
+
+    assert_ne!(first, second);
+}
+
+#[test]
+fn reset_replays_the_same_numbers() {
+    let mut chip8 = Chip8::new_with_seed(Quirks::default(), 7);
+    chip8.load_rom_slice(&PROGRAM).unwrap();
+    for _ in 0..8 {
+        chip8.step().unwrap();
+    }
+    let first: Vec<u8> = (0..8).map(|x| chip8.register(x)).collect();
+
+    chip8.reset(true);
+    for _ in 0..8 {
+        chip8.step().unwrap();
+    }
+    let second: Vec<u8> = (0..8).map(|x| chip8.register(x)).collect();
+
+    assert_eq!(first, second);
+}
@@ -0,0 +1,64 @@
+//! Recording the keypad input of a run and replaying it.
+
+use chip_8::{Chip8, Quirks, Recorder};
+
+// Loop drawing random numbers, and add them up in V1 while key 5 is held
+const PROGRAM: [u8; 14] = [
+    0xC0, 0xFF, // V0 = random
+    0x62, 0x05, // V2 = 5
+    0xE2, 0x9E, // skip the next instruction if key V2 is pressed
+    0x12, 0x00, // loop
+    0x71, 0x01, // V1 += 1
+    0x81, 0x04, // V1 += V0
+    0x12, 0x00, // loop
+];
+
+fn run(chip8: &mut Chip8, steps: usize) {
+    for _ in 0..steps {
+        chip8.step().unwrap();
+    }
+}
+
+fn registers(chip8: &Chip8) -> Vec<u8> {
+    (0..16).map(|x| chip8.register(x)).collect()
+}
+
+#[test]
+fn replay_reproduces_the_recorded_run() {
+    let mut original = Chip8::new(Quirks::default());
+    original.load_rom_slice(&PROGRAM).unwrap();
+    original.start_recording();
+    run(&mut original, 50);
+    original.set_key(5, true);
+    run(&mut original, 30);
+    original.set_key(5, true); // repeated presses are not recorded twice
+    run(&mut original, 7);
+    original.set_key(5, false);
+    run(&mut original, 40);
+    let recording = original.stop_recording().unwrap();
+    assert_eq!(recording.events().len(), 2);
+
+    let mut replayed = Chip8::new(Quirks::default());
+    replayed.load_rom_slice(&PROGRAM).unwrap();
+    replayed.start_replay(&recording);
+    run(&mut replayed, 127);
+
+    assert!(!replayed.is_replaying());
+    assert_ne!(registers(&original)[1], 0);
+    assert_eq!(registers(&replayed), registers(&original));
+}
+
+#[test]
+fn recording_survives_a_round_trip_through_a_file() {
+    let mut recording = Recorder::new(Some(1234));
+    recording.record(10, 0xA, true);
+    recording.record(25, 0xA, false);
+    recording.record(25, 0x0, true);
+
+    let path = std::env::temp_dir().join(format!("chip8-recording-{}.rec", std::process::id()));
+    recording.save(&path).unwrap();
+    let loaded = Recorder::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.unwrap(), recording);
+}
@@ -0,0 +1,42 @@
+//! Bookkeeping of the frame diff-drawing renderers paint over.
+
+use chip_8::{Framebuffer, PreviousFrame};
+
+#[test]
+fn frames_are_painted_over_the_previous_one() {
+    let mut previous = PreviousFrame::new();
+    let first = Framebuffer::new();
+    let mut second = first;
+    second.set_pixel(3, 4, true);
+
+    assert_eq!(previous.replace(&first), None); // nothing painted yet
+    assert_eq!(previous.replace(&second), Some(first));
+    assert_eq!(previous.replace(&second), Some(second));
+}
+
+#[test]
+fn the_frame_after_a_paused_one_is_painted_whole() {
+    // what the display does around a paused frame: paint it, dim it, then forget it
+    let mut previous = PreviousFrame::new();
+    let mut frame = Framebuffer::new();
+    frame.set_pixel(0, 0, true);
+    previous.replace(&frame);
+    previous.replace(&frame);
+    previous.forget();
+
+    // even an unchanged frame is painted whole, so the dimmed pixels are all repainted
+    assert_eq!(previous.replace(&frame), None);
+    assert_eq!(previous.replace(&frame), Some(frame));
+}
+
+#[test]
+fn a_change_of_resolution_is_painted_whole() {
+    let mut previous = PreviousFrame::new();
+    let low_res = Framebuffer::new();
+    let mut high_res = low_res;
+    high_res.set_hi_res(true);
+
+    previous.replace(&low_res);
+    assert_eq!(previous.replace(&high_res), None);
+    assert_eq!(previous.replace(&high_res), Some(high_res));
+}
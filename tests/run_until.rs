@@ -0,0 +1,83 @@
+//! Controlled execution for debuggers and headless tests: `run_until`, breakpoints and `peek_opcode`.
+
+use chip_8::{Chip8, Quirks};
+
+// Count up in V0 forever
+const COUNTER: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+// Jump to the jump itself, ending the program
+const HALT: [u8; 2] = [0x12, 0x00];
+
+fn load(program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom_slice(program).unwrap();
+    chip8
+}
+
+#[test]
+fn stops_when_the_predicate_is_met() {
+    let mut chip8 = load(&COUNTER);
+
+    assert_eq!(
+        chip8.run_until(1000, |chip8| chip8.register(0) == 10),
+        Ok(true)
+    );
+    assert_eq!(chip8.register(0), 10);
+    assert_eq!(chip8.instruction_count(), 19);
+}
+
+#[test]
+fn gives_up_after_max_cycles() {
+    let mut chip8 = load(&COUNTER);
+
+    assert_eq!(
+        chip8.run_until(100, |chip8| chip8.register(0) == 200),
+        Ok(false)
+    );
+    assert_eq!(chip8.instruction_count(), 100);
+}
+
+#[test]
+fn gives_up_when_the_rom_halts() {
+    let mut chip8 = load(&HALT);
+
+    assert_eq!(
+        chip8.run_until(100_000, |chip8| chip8.register(0) == 1),
+        Ok(false)
+    );
+    assert_eq!(chip8.instruction_count(), 1);
+}
+
+#[test]
+fn halting_can_be_waited_for() {
+    let mut chip8 = load(&HALT);
+
+    assert_eq!(chip8.run_until(100, Chip8::is_halted), Ok(true));
+}
+
+#[test]
+fn runs_up_to_a_breakpoint() {
+    let mut chip8 = load(&COUNTER);
+    chip8.add_breakpoint(0x202);
+
+    assert_eq!(chip8.run_until(100, Chip8::is_at_breakpoint), Ok(true));
+    assert_eq!(chip8.program_counter(), 0x202);
+    assert!(chip8.take_break_request());
+
+    chip8.remove_breakpoint(0x202);
+    assert_eq!(chip8.run_until(100, Chip8::is_at_breakpoint), Ok(false));
+    assert_eq!(chip8.breakpoints().count(), 0);
+}
+
+#[test]
+fn peeking_does_not_execute() {
+    let mut chip8 = load(&COUNTER);
+
+    assert_eq!(chip8.peek_opcode(), Ok(0x7001));
+    assert_eq!(chip8.peek_opcode(), Ok(0x7001));
+    assert_eq!(chip8.program_counter(), 0x200);
+    assert_eq!(chip8.register(0), 0);
+
+    chip8.step().unwrap();
+    assert_eq!(chip8.peek_opcode(), Ok(0x1200));
+}
@@ -0,0 +1,87 @@
+//! Save states and rewinding, which must resume exactly where the CPU left off.
+#![cfg(feature = "serde")]
+
+use chip_8::{Chip8, Quirks};
+
+// Add random numbers up in V1 forever
+const RANDOM_SUM: [u8; 6] = [
+    0xC0, 0xFF, // V0 = random
+    0x81, 0x04, // V1 += V0
+    0x12, 0x00, // loop
+];
+
+// Wait for a key in V0, then loop
+const GET_KEY: [u8; 4] = [0xF0, 0x0A, 0x12, 0x02];
+
+fn load(program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new_with_seed(Quirks::default(), 42);
+    chip8.load_rom_slice(program).unwrap();
+    chip8
+}
+
+fn run(chip8: &mut Chip8, steps: usize) -> [u8; 16] {
+    for _ in 0..steps {
+        chip8.step().unwrap();
+    }
+    *chip8.registers()
+}
+
+#[test]
+fn random_numbers_resume_after_loading_a_state() {
+    let mut chip8 = load(&RANDOM_SUM);
+    run(&mut chip8, 30);
+    let state = chip8.save_state();
+    let expected = run(&mut chip8, 30);
+
+    chip8.load_state(&state).unwrap();
+    assert_eq!(run(&mut chip8, 30), expected);
+}
+
+#[test]
+fn rewind_replays_the_same_random_numbers() {
+    let mut chip8 = load(&RANDOM_SUM);
+    chip8.set_history_capacity(10);
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+    let expected = *chip8.registers();
+
+    for _ in 0..3 {
+        assert!(chip8.rewind());
+    }
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+    assert_eq!(*chip8.registers(), expected);
+}
+
+#[test]
+fn key_wait_resumes_after_loading_a_state() {
+    let mut chip8 = load(&GET_KEY);
+    chip8.set_key(7, true);
+    run(&mut chip8, 1); // FX0A saw the press and waits for the release
+    let state = chip8.save_state();
+
+    let mut restored = load(&GET_KEY);
+    restored.load_state(&state).unwrap();
+    assert!(restored.is_waiting_for_key());
+    restored.set_key(7, false);
+    assert_eq!(run(&mut restored, 1)[0], 7);
+    assert!(!restored.is_waiting_for_key());
+}
+
+#[test]
+fn display_wait_resumes_after_loading_a_state() {
+    // draw with the display_wait quirk of the COSMAC VIP, then count
+    let mut chip8 = load(&[0xD0, 0x01, 0x71, 0x01]);
+    chip8.cycle().unwrap();
+    let state = chip8.save_state();
+
+    let mut restored = load(&[0xD0, 0x01, 0x71, 0x01]);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.cycle(), Ok(false)); // still waiting for the vertical blank
+    assert_eq!(restored.register(1), 0);
+    restored.tick_timers();
+    restored.cycle().unwrap();
+    assert_eq!(restored.register(1), 1);
+}
@@ -0,0 +1,63 @@
+//! Persistence of the opcode histogram across runs.
+
+use chip_8::{load_histogram, save_histogram, Chip8, Quirks, OPCODE_CATEGORIES};
+use std::path::PathBuf;
+
+// Count up in V0 forever: one 7XNN and one 1NNN per loop
+const COUNTER: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+fn stats_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chip8-{}-{}.stats", name, std::process::id()))
+}
+
+fn run_counter(steps: usize) -> Chip8 {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom_slice(&COUNTER).unwrap();
+    for _ in 0..steps {
+        chip8.step().unwrap();
+    }
+    chip8
+}
+
+#[test]
+fn saved_counts_are_merged_into_the_next_run() {
+    let path = stats_file("merge");
+    let first = run_counter(10);
+    save_histogram(&path, first.opcode_histogram()).unwrap();
+    assert_eq!(&load_histogram(&path), first.opcode_histogram());
+
+    let mut second = run_counter(4);
+    second.merge_opcode_histogram(&load_histogram(&path));
+    std::fs::remove_file(&path).unwrap();
+
+    let histogram = second.opcode_histogram();
+    assert_eq!(histogram[0x7], 7);
+    assert_eq!(histogram[0x1], 7);
+    assert_eq!(histogram.iter().sum::<u64>(), 14);
+}
+
+#[test]
+fn missing_or_corrupt_file_starts_fresh() {
+    let path = stats_file("corrupt");
+    assert_eq!(load_histogram(&path), [0; OPCODE_CATEGORIES]);
+
+    std::fs::write(&path, "12\nnot a number\n").unwrap();
+    assert_eq!(load_histogram(&path), [0; OPCODE_CATEGORIES]);
+    std::fs::write(&path, "1\n2\n3\n").unwrap(); // too few categories
+    assert_eq!(load_histogram(&path), [0; OPCODE_CATEGORIES]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn huge_counts_saturate_instead_of_overflowing() {
+    let path = stats_file("huge");
+    save_histogram(&path, &[u64::MAX; OPCODE_CATEGORIES]).unwrap();
+
+    let mut chip8 = run_counter(4);
+    chip8.merge_opcode_histogram(&load_histogram(&path));
+    std::fs::remove_file(&path).unwrap();
+    chip8.step().unwrap(); // counting on does not overflow either
+
+    assert_eq!(chip8.opcode_histogram()[0x7], u64::MAX);
+    assert_eq!(chip8.opcode_histogram()[0x1], u64::MAX);
+}
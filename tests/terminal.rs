@@ -0,0 +1,42 @@
+//! Reading the keypad from a terminal, which sends characters but no key releases.
+
+use std::time::{Duration, Instant};
+
+use chip_8::{HeldKeys, TerminalKeymap, KEY_HOLD};
+
+#[test]
+fn layouts_map_the_same_block_of_keys() {
+    let qwerty = TerminalKeymap::from_layout("qwerty").unwrap();
+    let azerty = TerminalKeymap::from_layout("AZERTY").unwrap();
+    assert_eq!(qwerty.map('1'), Some(0x1));
+    assert_eq!(qwerty.map('q'), Some(0x4));
+    assert_eq!(qwerty.map('X'), Some(0x0)); // caps lock
+    assert_eq!(qwerty.map('v'), Some(0xF));
+    assert_eq!(azerty.map('&'), Some(0x1)); // top row without shift
+    assert_eq!(azerty.map('1'), Some(0x1));
+    assert_eq!(azerty.map('a'), Some(0x4));
+    assert_eq!(azerty.map('w'), Some(0xA));
+    assert_eq!(qwerty.map('p'), None);
+    assert!(TerminalKeymap::from_layout("dvorak").is_none());
+}
+
+#[test]
+fn keys_are_released_once_they_stop_repeating() {
+    let start = Instant::now();
+    let mut held = HeldKeys::new();
+    assert!(held.press(0x5, start));
+    assert!(held.release_expired(start + KEY_HOLD / 2).is_empty());
+
+    // auto-repeat keeps the key held past the first hold period
+    assert!(!held.press(0x5, start + KEY_HOLD / 2));
+    assert!(held.release_expired(start + KEY_HOLD).is_empty());
+
+    assert_eq!(
+        held.release_expired(start + KEY_HOLD / 2 + KEY_HOLD),
+        vec![0x5]
+    );
+    assert!(held
+        .release_expired(start + KEY_HOLD * 2 + Duration::from_secs(1))
+        .is_empty());
+    assert!(held.press(0x5, start + KEY_HOLD * 3)); // pressed again
+}
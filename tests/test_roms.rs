@@ -0,0 +1,118 @@
+//! Golden-image tests: well-known test ROMs are run headlessly and the final screen
+//! is compared with the fingerprint of a known-good run.
+//! When a fingerprint changes on purpose, run the test with `--nocapture` and check
+//! the printed screen before updating it.
+
+use chip_8::{rom_fingerprint, Chip8, Quirks};
+
+const FRAMES: usize = 600; // ten seconds of emulated time, enough for every test to finish
+const CYCLES_PER_FRAME: usize = 1000; // fast enough for the timing measurements of the quirks test
+const PLATFORM_ADDRESS: u16 = 0x1FF; // Timendus tests skip their menu when the platform is stored here
+
+/// Run a ROM from `tests/roms/` and return the CPU once the time is up
+fn run(rom: &str, quirks: Quirks, platform: Option<u8>) -> Chip8 {
+    let bytes = std::fs::read(format!("tests/roms/{}", rom)).expect("unable to read test ROM");
+    let mut chip8 = Chip8::new(quirks);
+    chip8.load_rom(bytes).expect("test ROM fits in memory");
+    if let Some(platform) = platform {
+        chip8.write_memory(PLATFORM_ADDRESS, platform).unwrap();
+    }
+
+    for _ in 0..FRAMES {
+        chip8
+            .run_frame(CYCLES_PER_FRAME)
+            .expect("test ROM runs without error");
+    }
+    chip8
+}
+
+/// Compare the screen with a golden fingerprint, printing it to help diagnose a mismatch
+fn assert_screen(chip8: &Chip8, expected: u64) {
+    let (width, height) = chip8.dimensions();
+    let pixels = chip8.framebuffer();
+    for row in pixels.chunks(width).take(height) {
+        let line: String = row
+            .iter()
+            .map(|&p| if p != 0 { '#' } else { '.' })
+            .collect();
+        println!("{}", line);
+    }
+
+    let fingerprint = rom_fingerprint(&pixels);
+    assert_eq!(
+        fingerprint, expected,
+        "screen fingerprint {:#018x} differs from the golden image",
+        fingerprint
+    );
+}
+
+#[test]
+fn ibm_logo() {
+    let chip8 = run("ibm_logo.ch8", Quirks::cosmac_vip(), None);
+    assert!(chip8.is_halted());
+    assert_screen(&chip8, 0x3833_6998_6d63_b8e1);
+}
+
+#[test]
+fn ibm_logo_as_text() {
+    let chip8 = run("ibm_logo.ch8", Quirks::cosmac_vip(), None);
+    let screen = chip8.display_to_string();
+    let rows: Vec<&str> = screen.split('\n').collect();
+    assert_eq!(rows.len(), 32);
+    assert!(rows.iter().all(|row| row.len() == 64));
+
+    // the logo sits on rows 8 to 22, the trailing spaces are trimmed
+    let logo: Vec<&str> = rows[8..23].iter().map(|row| row.trim_end()).collect();
+    let expected = [
+        "            ######## #########   #####         #####",
+        "",
+        "            ######## ########### ######       ######",
+        "",
+        "              ####     ###   ###   #####     #####",
+        "",
+        "              ####     #######     ####### #######",
+        "",
+        "              ####     #######     ### ####### ###",
+        "",
+        "              ####     ###   ###   ###  #####  ###",
+        "",
+        "            ######## ########### #####   ###   #####",
+        "",
+        "            ######## #########   #####    #    #####",
+    ];
+    assert_eq!(logo, expected);
+    assert!(rows[..8]
+        .iter()
+        .chain(&rows[23..])
+        .all(|row| row.trim().is_empty()));
+}
+
+#[test]
+fn corax_opcode_test() {
+    let chip8 = run("corax_opcode_test.ch8", Quirks::cosmac_vip(), None);
+    assert_screen(&chip8, 0xa67c_965c_779a_ad4c);
+}
+
+#[test]
+fn flags_test() {
+    let chip8 = run("flags_test.ch8", Quirks::cosmac_vip(), None);
+    assert_screen(&chip8, 0xebf0_c1f1_89e2_0fc4);
+}
+
+#[test]
+fn quirks_test_super_chip() {
+    let chip8 = run("quirks_test.ch8", Quirks::super_chip(), Some(2));
+    assert_screen(&chip8, 0x3cfc_c86c_d32b_73bb);
+}
+
+#[test]
+fn quirks_test_xo_chip() {
+    let chip8 = run("quirks_test.ch8", Quirks::xo_chip(), Some(3));
+    assert_screen(&chip8, 0x3dc6_3b7f_f399_913b);
+}
+
+#[test]
+fn quirks_test_chip_8() {
+    let chip8 = run("quirks_test.ch8", Quirks::cosmac_vip(), Some(1));
+    assert_screen(&chip8, 0xb170_490e_6760_ec81);
+}
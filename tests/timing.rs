@@ -0,0 +1,36 @@
+//! Frame pacing of the display, driven with injected instants instead of the wall clock.
+
+use chip_8::FrameLimiter;
+use std::time::{Duration, Instant};
+
+#[test]
+fn waits_for_the_rest_of_the_frame() {
+    let mut limiter = FrameLimiter::new(50); // 20 ms per frame
+    let start = Instant::now() + Duration::from_millis(20); // the first frame is due
+    assert!(limiter.try_begin_frame(start));
+
+    assert_eq!(
+        limiter.time_until_next_frame(start + Duration::from_millis(5)),
+        Duration::from_millis(15)
+    );
+    assert!(!limiter.try_begin_frame(start + Duration::from_millis(5)));
+    // a late frame is due at once
+    assert_eq!(
+        limiter.time_until_next_frame(start + Duration::from_millis(30)),
+        Duration::ZERO
+    );
+}
+
+#[test]
+fn presents_the_target_frames_per_second() {
+    for fps in [30, 60, 120] {
+        let mut limiter = FrameLimiter::new(fps);
+        let start = Instant::now() + Duration::from_secs(1);
+
+        // the main loop polls every 100 µs for one second
+        let frames = (0..10_000)
+            .filter(|&tick| limiter.try_begin_frame(start + Duration::from_micros(tick * 100)))
+            .count();
+        assert_eq!(frames, fps as usize);
+    }
+}
@@ -0,0 +1,79 @@
+//! Memory watchpoints reporting the instructions that read or write watched addresses.
+
+use chip_8::{Chip8, MemoryAccess, Quirks, WatchHit};
+
+fn load(quirks: Quirks, program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks);
+    chip8.load_rom_slice(program).unwrap();
+    chip8
+}
+
+#[test]
+fn register_store_and_load_hit_the_watchpoint() {
+    let mut chip8 = load(
+        Quirks::super_chip(),
+        &[
+            0xA3, 0x00, // I = 0x300
+            0xF3, 0x55, // store V0..V3 at 0x300..0x303
+            0xF1, 0x65, // load V0..V1 from 0x300..0x301
+        ],
+    );
+    chip8.add_watchpoint(0x302);
+
+    chip8.step().unwrap();
+    assert!(chip8.take_watch_hits().is_empty());
+    assert!(!chip8.take_break_request());
+
+    chip8.step().unwrap();
+    assert_eq!(
+        chip8.take_watch_hits(),
+        vec![WatchHit {
+            addr: 0x302,
+            access: MemoryAccess::Write,
+            instruction: 0x202,
+        }]
+    );
+    assert!(chip8.take_break_request());
+
+    // V0..V1 do not reach 0x302
+    chip8.step().unwrap();
+    assert!(chip8.take_watch_hits().is_empty());
+}
+
+#[test]
+fn fetching_a_watched_instruction_is_a_read() {
+    let mut chip8 = load(Quirks::default(), &[0x60, 0x01, 0x61, 0x02]);
+    chip8.add_watchpoint(0x203);
+
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    assert_eq!(
+        chip8.take_watch_hits(),
+        vec![WatchHit {
+            addr: 0x203,
+            access: MemoryAccess::Read,
+            instruction: 0x202,
+        }]
+    );
+
+    chip8.remove_watchpoint(0x203);
+    assert_eq!(chip8.watchpoints().count(), 0);
+}
+
+#[test]
+fn long_index_load_reads_its_operand() {
+    let mut chip8 = load(Quirks::xo_chip(), &[0xF0, 0x00, 0x12, 0x34]);
+    chip8.add_watchpoint(0x203);
+
+    chip8.step().unwrap();
+    assert_eq!(chip8.index_register(), 0x1234);
+    assert_eq!(chip8.program_counter(), 0x204);
+    assert_eq!(
+        chip8.take_watch_hits(),
+        vec![WatchHit {
+            addr: 0x203,
+            access: MemoryAccess::Read,
+            instruction: 0x200,
+        }]
+    );
+}